@@ -0,0 +1,137 @@
+// NOTE: `local-rs --query "..."` prints the results instead of opening the window, so the index
+// can be searched from scripts and piped into other tools
+
+pub const USAGE: &str =
+    "usage: local-rs [--query <query> [--limit <n>] [--offset <n>] [--format plain|json|tsv]]";
+
+#[derive(Debug, Default, Clone, Copy)]
+pub enum OutputFormat {
+    // one path per line
+    #[default]
+    Plain,
+    // an array of `{"path": ..., "score": ..., "matched_terms": [...], "snippet": ...}` objects
+    Json,
+    // `<path>\t<score>` lines
+    Tsv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "json" => Ok(Self::Json),
+            "tsv" => Ok(Self::Tsv),
+            x => Err(format!("Unknown output format {x}")),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    query: String,
+    // how many results get printed at most, all of them if unset
+    limit: Option<usize>,
+    // how many of the best results get skipped, for paging through them together with `limit`
+    offset: usize,
+    format: OutputFormat,
+}
+
+impl Options {
+    /// `None` if there's no `--query`, then the window gets opened as usual
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut opts = Self::default();
+        let mut query = None;
+        let mut any_args = false;
+        while let Some(arg) = args.next() {
+            any_args = true;
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--query" => query = Some(value()?),
+                "--limit" => opts.limit = Some(parse_number(&arg, &value()?)?),
+                "--offset" => opts.offset = parse_number(&arg, &value()?)?,
+                "--format" => opts.format = value()?.parse()?,
+                x => return Err(format!("Unknown argument {x}")),
+            }
+        }
+        match query {
+            Some(q) => {
+                opts.query = q;
+                Ok(Some(opts))
+            }
+            None if any_args => {
+                Err("--limit, --offset and --format only work together with --query".to_string())
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_number(arg: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value {value} for {arg}: {e}"))
+}
+
+/// Runs the query against the index (building it first if there is none) and prints the results
+pub fn run(opts: &Options) -> std::process::ExitCode {
+    let Some((_, _, index_file, config)) = crate::App::init_settings() else {
+        return std::process::ExitCode::FAILURE;
+    };
+    let (mut model, _) = crate::App::init_model(&index_file, &config);
+    crate::merge_indexes(&mut model, &config.merged_indexes, config.index_root());
+    if model.is_empty() {
+        log::warn!(
+            "No indexable documents found in {}",
+            config.document_directories.join(", ")
+        );
+    }
+    let dict = crate::search_model::TermDictionary::build(&model);
+    let query_terms = crate::split_query(&opts.query, config.cjk_bigrams);
+    let terms: Vec<&str> = query_terms.iter().map(String::as_str).collect();
+    // no time limit, nobody is waiting on a frame to be drawn
+    let mode = crate::search_model::QueryMode::default();
+    let (docs, _) = crate::search_model::query(
+        &model,
+        &dict,
+        &terms,
+        mode,
+        None,
+        config.scoring(),
+        config.show_scores.unwrap_or_default(),
+    );
+    let page = docs
+        .into_iter()
+        .skip(opts.offset)
+        .take(opts.limit.unwrap_or(usize::MAX));
+    match opts.format {
+        OutputFormat::Plain => page.for_each(|r| println!("{}", r.path)),
+        OutputFormat::Tsv => page.for_each(|r| println!("{}\t{}", r.path, r.score)),
+        OutputFormat::Json => {
+            let highlighted = crate::highlight_terms(&terms);
+            let highlighted: Vec<&str> = highlighted.iter().map(String::as_str).collect();
+            // only the json has room for them, the text of every result has to be read again
+            let results: Vec<_> = page
+                .map(|mut r| {
+                    r.snippet = crate::search_model::read_document_text(&r.path).and_then(|text| {
+                        crate::search_model::snippet(
+                            &text,
+                            &highlighted,
+                            mode,
+                            config.snippet_words(),
+                        )
+                    });
+                    r
+                })
+                .collect();
+            match serde_json::to_string(&results) {
+                Ok(json) => println!("{json}"),
+                Err(e) => {
+                    log::error!("Failed to serialize the results: {e}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+    std::process::ExitCode::SUCCESS
+}
@@ -0,0 +1,112 @@
+// NOTE: A tiny `log` backend instead of pulling in a whole logging framework, lines keep the old
+// `[ERR]: ...` look on stderr and can be copied into a log file too
+
+// only our own messages, the dependencies log a lot of noise
+const TARGET: &str = "local_rs";
+
+/// Overrides the `log_level` from the config (e.g. `LOCAL_SEARCH_LOG=debug`)
+pub const LEVEL_ENV: &str = "LOCAL_SEARCH_LOG";
+
+struct Logger {
+    file: std::sync::Mutex<Option<std::fs::File>>,
+}
+
+static LOGGER: Logger = Logger {
+    file: std::sync::Mutex::new(None),
+};
+
+fn tag(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "ERR",
+        log::Level::Warn => "WARN",
+        log::Level::Info => "INFO",
+        log::Level::Debug => "DEBUG",
+        log::Level::Trace => "TRACE",
+    }
+}
+
+// utc `YYYY-MM-DD HH:MM:SS`
+fn timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let time = secs % 86400;
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        crate::search_model::format_date(secs),
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with(TARGET) && metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{}]: {}", tag(record.level()), record.args());
+        eprintln!("{line}");
+        if let Ok(mut file) = self.file.lock()
+            && let Some(f) = file.as_mut()
+        {
+            // nowhere left to report a failed write to
+            let _ = std::io::Write::write_all(f, format!("{} {line}\n", timestamp()).as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock()
+            && let Some(f) = file.as_mut()
+        {
+            let _ = std::io::Write::flush(f);
+        }
+    }
+}
+
+/// Starts logging to stderr at the info level (or the one from the environment), has to run before
+/// anything gets logged
+pub fn init() {
+    if log::set_logger(&LOGGER).is_err() {
+        return;
+    }
+    log::set_max_level(env_level().unwrap_or(log::LevelFilter::Info));
+}
+
+fn env_level() -> Option<log::LevelFilter> {
+    let level = std::env::var(LEVEL_ENV).ok()?;
+    match level.parse() {
+        Ok(l) => Some(l),
+        Err(_) => {
+            log::warn!("Unknown log level {level} in {LEVEL_ENV}");
+            None
+        }
+    }
+}
+
+/// Applies the logging settings from the config, the environment still wins for the level
+pub fn configure(level: Option<log::LevelFilter>, file: Option<&std::path::Path>) {
+    if let Some(level) = env_level().or(level) {
+        log::set_max_level(level);
+    }
+    let Some(p) = file else {
+        return;
+    };
+    match std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(p)
+    {
+        Ok(f) => {
+            if let Ok(mut file) = LOGGER.file.lock() {
+                *file = Some(f);
+            }
+            log::info!("Logging to {}", p.display());
+        }
+        Err(e) => log::error!("Failed to open log file {}: {e}", p.display()),
+    }
+}
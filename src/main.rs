@@ -1,8 +1,13 @@
+mod cli;
+mod logging;
 mod search_model;
 use raylib::prelude::{RaylibDraw, RaylibScissorModeExt};
 use raylib::text::RaylibFont;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::{collections::HashMap, io::Read, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 #[derive(Default, Debug, Clone, Copy)]
 struct Color {
@@ -19,6 +24,53 @@ impl Color {
     pub const fn into_raylib(self) -> raylib::color::Color {
         raylib::color::Color::new(self.r, self.g, self.b, 255)
     }
+
+    fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    // see: https://www.w3.org/TR/WCAG21/#dfn-relative-luminance
+    fn luminance(self) -> f32 {
+        let channel = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+
+    // the wcag contrast ratio, from 1 (the same color) to 21 (black on white)
+    fn contrast(self, other: Self) -> f32 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    fn blend(self, other: Self, amount: f32) -> Self {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount) as u8;
+        Self::new(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+        )
+    }
+
+    // this color as the background of `text`, darkened or lightened (whichever `text` stands out
+    // more against) until the contrast is at least `min`
+    fn readable_behind(self, text: Self, min: f32) -> Self {
+        let (black, white) = (Self::new(0, 0, 0), Self::new(0xff, 0xff, 0xff));
+        let towards = if text.contrast(black) > text.contrast(white) {
+            black
+        } else {
+            white
+        };
+        (0..=20)
+            .map(|i| self.blend(towards, i as f32 / 20.0))
+            .find(|c| c.contrast(text) >= min)
+            .unwrap_or(towards)
+    }
 }
 
 impl Serialize for Color {
@@ -26,8 +78,7 @@ impl Serialize for Color {
     where
         S: Serializer,
     {
-        let s = format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b);
-        serializer.serialize_str(&s)
+        serializer.serialize_str(&self.hex())
     }
 }
 
@@ -53,7 +104,7 @@ impl<'de> Deserialize<'de> for Color {
     }
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 enum Theme {
     #[default]
@@ -70,6 +121,9 @@ impl Theme {
         idle_color: Color::new(0x20, 0x20, 0x20),
         hovered_color: Color::new(0x30, 0x30, 0x30),
         clicked_color: Color::new(0x40, 0x40, 0x40),
+        highlight_color: Some(Color::new(0x5c, 0x4b, 0x1e)),
+        selection_color: Some(Color::new(0x2c, 0x3e, 0x55)),
+        font_name: None,
     };
 
     const CAT_LATTE_COLORS: ThemeColors = ThemeColors {
@@ -78,6 +132,9 @@ impl Theme {
         idle_color: Color::new(0xdc, 0x8a, 0x78),       // ROSEWATER
         hovered_color: Color::new(0xdd, 0x78, 0x78),    // FLAMINGO
         clicked_color: Color::new(0xea, 0x76, 0xcb),    // PINK
+        highlight_color: Some(Color::new(0xdf, 0x8e, 0x1d)), // YELLOW
+        selection_color: Some(Color::new(0x72, 0x87, 0xfd)), // LAVENDER
+        font_name: None,
     };
 
     const CAT_MOCHA_COLORS: ThemeColors = ThemeColors {
@@ -86,6 +143,9 @@ impl Theme {
         idle_color: Color::new(0x31, 0x32, 0x44),       // SURFACE 0
         hovered_color: Color::new(0x45, 0x47, 0x5a),    // SURFACE 1
         clicked_color: Color::new(0x58, 0x5b, 0x70),    // SURFACE 2
+        highlight_color: Some(Color::new(0x58, 0x5b, 0x70)), // SURFACE 2
+        selection_color: Some(Color::new(0x6c, 0x70, 0x86)), // OVERLAY 0
+        font_name: None,
     };
 
     pub fn get_all_colors(&self) -> &ThemeColors {
@@ -93,351 +153,2426 @@ impl Theme {
             Self::Default => &Self::DEFAULT_COLORS,
             Self::CatppuccinLatte => &Self::CAT_LATTE_COLORS,
             Self::CatppuccinMocha => &Self::CAT_MOCHA_COLORS,
-            Self::Custom(c) => &c,
+            Self::Custom(c) => c,
         }
     }
+
+    // the font the theme comes with, it wins over the global `font_name`
+    pub fn font_name(&self) -> Option<&str> {
+        self.get_all_colors().font_name.as_deref()
+    }
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct ThemeColors {
     background_color: Color,
     foreground_color: Color,
     idle_color: Color,
     hovered_color: Color,
     clicked_color: Color,
-}
-
-// NOTE: Here we use serde (toml) since its a config file come on guys
-#[derive(Serialize, Deserialize, Default, Debug)]
-struct Config {
-    document_directories: Vec<String>,
+    // matched terms in the results, clicked_color if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    highlight_color: Option<Color>,
+    // the result picked with the arrow keys, hovered_color if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    selection_color: Option<Color>,
+    // fontconfig name of the theme's own font, the config's `font_name` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     font_name: Option<String>,
-    theme: Theme,
 }
 
-const FONT: &[u8] = include_bytes!("../assets/GeistMonoNerdFontMono-Regular.otf");
-
-struct App {
-    raylib_thread: raylib::prelude::RaylibThread,
-    raylib_handle: raylib::prelude::RaylibHandle,
-    font: raylib::text::Font,
-    bg_color: raylib::color::Color,
-    fg_color: raylib::color::Color,
-    idle_color: raylib::color::Color,
-    hover_color: raylib::color::Color,
-    click_color: raylib::color::Color,
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum IndexFormat {
+    // human readable, handy for debugging the index
+    Json,
+    // smaller and a lot faster to load
+    #[default]
+    Binary,
+}
 
-    model: HashMap<String, search_model::Document>,
-    docs: Vec<String>,
-    query: String,
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Layout {
+    // results take up the whole width
+    #[default]
+    List,
+    // results on the left, a preview of the selected one on the right
+    Split,
+}
 
-    query_box_selected: bool,
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ResultHighlight {
+    // the words matching the query terms get highlighted
+    #[default]
+    Terms,
+    // the rows get tinted with the highlight color, the better the score the stronger
+    Row,
+    Both,
+}
 
-    scroll_velocity: raylib::math::Vector2,
-    doc_offset: f32,
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Ranking {
+    #[default]
+    #[serde(rename = "tf-idf")]
+    TfIdf,
+    Bm25,
+}
 
-    conf: Config,
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum EmptyQuery {
+    // every document, pinned ones first and the rest newest first
+    #[default]
+    Browse,
+    // only the most recently modified documents, for reopening what you just worked on
+    Recent,
+    // no results until something gets typed
+    Nothing,
+}
 
-    display_profile_data: bool,
+impl ResultHighlight {
+    fn terms(self) -> bool {
+        matches!(self, Self::Terms | Self::Both)
+    }
 
-    index_file: std::path::PathBuf,
-    boot_time: std::time::Duration,
-    boot_index_time: std::time::Duration,
-    update_time: std::time::Duration,
-    draw_time: std::time::Duration,
-    last_query_time: std::time::Duration,
-    reindex_time: std::time::Duration,
+    fn row(self) -> bool {
+        matches!(self, Self::Row | Self::Both)
+    }
 }
 
-impl App {
-    fn init_directories() -> Option<(std::path::PathBuf, std::path::PathBuf, std::path::PathBuf)> {
-        let app_dirs = match platform_dirs::AppDirs::new(Some("local-search"), false) {
-            Some(dirs) => dirs,
-            None => {
-                eprintln!("[ERR]: Failed to get app directories (for config, state)");
-                return None;
-            }
-        };
-        let mut document_base_dir = match platform_dirs::UserDirs::new() {
-            Some(d) => d.document_dir,
-            None => {
-                eprintln!("[ERR]: Failed to get user directories (for document storage)");
-                return None;
+impl Layout {
+    // the column the results go in (shaped like the search bar so `row_rect` works on it) and the
+    // preview pane next to it, if there is one
+    fn split(
+        self,
+        search_rect: raylib::math::Rectangle,
+        w_h: f32,
+    ) -> (raylib::math::Rectangle, Option<raylib::math::Rectangle>) {
+        match self {
+            Self::List => (search_rect, None),
+            Self::Split => {
+                let gap = search_rect.height * 0.1;
+                let mut list = search_rect;
+                list.width = (search_rect.width - gap) / 2.0;
+                let top = row_rect(search_rect, 0).y;
+                let preview = raylib::math::Rectangle::new(
+                    list.x + list.width + gap,
+                    top,
+                    search_rect.width - list.width - gap,
+                    (w_h - top - search_rect.x).max(0.0),
+                );
+                (list, Some(preview))
             }
-        };
-        document_base_dir.push("local-search");
-
-        let config_file = app_dirs.config_dir.join("config.toml");
-        let index_file = app_dirs.state_dir.join("index.bin");
-        if !app_dirs.config_dir.exists() {
-            eprintln!(
-                "[INFO]: Config directory {} does not exist, creating it...",
-                &app_dirs.config_dir.display()
-            );
-            match std::fs::create_dir_all(&app_dirs.config_dir) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERR]: Failed to create config dir {}: {e}",
-                        app_dirs.config_dir.display()
-                    );
-                    return None;
-                }
-            };
-        }
-        if !app_dirs.state_dir.exists() {
-            eprintln!(
-                "[INFO]: State directory {} does not exist, creating it...",
-                &app_dirs.state_dir.display()
-            );
-            match std::fs::create_dir_all(&app_dirs.state_dir) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERR]: Failed to create state dir {}: {e}",
-                        app_dirs.state_dir.display()
-                    );
-                    return None;
-                }
-            };
-        }
-        if !document_base_dir.exists() {
-            eprintln!(
-                "[INFO]: Document directory {} does not exist, creating it...",
-                &document_base_dir.display()
-            );
-            std::fs::create_dir_all(&document_base_dir).unwrap();
-            match std::fs::create_dir_all(&document_base_dir) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERR]: Failed to create document storage dir {}: {e}",
-                        document_base_dir.display()
-                    );
-                    return None;
-                }
-            };
         }
-        Some((document_base_dir, config_file, index_file))
     }
 
-    fn init_config(
-        document_base_dir: &std::path::Path,
-        config_file: &std::path::Path,
-    ) -> Option<Config> {
-        let mut config = Config::default();
-        config
-            .document_directories
-            .push(document_base_dir.to_string_lossy().to_string());
-        if config_file.exists() {
-            let conf_file_content = match std::fs::read_to_string(config_file) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!(
-                        "[ERR]: Failed to read config file {}: {e}",
-                        config_file.display()
-                    );
-                    return None;
-                }
-            };
-            config = match toml::de::from_str(&conf_file_content) {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("[ERR]: Failed to parse config: {e}");
-                    return None;
-                }
-            };
-            for p in &mut config.document_directories {
-                let np = match std::path::PathBuf::from_str(p) {
-                    Ok(p) => p,
-                    Err(e) => {
-                        eprintln!("[ERR]: Failed to parse document directory string {p}: {e}");
-                        return None;
-                    }
-                };
-                let mut copy = document_base_dir.to_path_buf();
-                copy.push(np);
-                *p = copy.to_string_lossy().to_string();
-            }
-        } else {
-            match std::fs::write(
-                config_file,
-                match toml::ser::to_string_pretty(&config) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        eprintln!("[ERR]: Failed to serialize {config:?}: {e}");
-                        return None;
-                    }
-                },
-            ) {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!(
-                        "[ERR]: Failed to write config to {}: {e}",
-                        config_file.display()
-                    );
-                    return None;
-                }
-            };
+    fn toggled(self) -> Self {
+        match self {
+            Self::List => Self::Split,
+            Self::Split => Self::List,
         }
-        Some(config)
     }
+}
 
-    fn init_model(
-        index_file: &std::path::Path,
-        conf: &Config,
-    ) -> HashMap<String, search_model::Document> {
-        let mut model = HashMap::new();
-        if index_file.exists() {
-            let mut b_reader = std::io::BufReader::new(std::fs::File::open(index_file).unwrap());
-            let mut bytes = vec![];
-            b_reader.read_to_end(&mut bytes).unwrap();
-            model = wincode::deserialize(&bytes).unwrap();
-        } else {
-            for p in &conf.document_directories {
-                let m = search_model::analyze_dir(&std::path::PathBuf::from(p)).unwrap();
-                m.into_iter().for_each(|(k, v)| {
-                    model.insert(k, v);
-                });
-            }
-            std::fs::write(index_file, wincode::serialize(&model).unwrap()).unwrap();
+impl IndexFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "index.json",
+            Self::Binary => "index.bin",
         }
-        model
     }
 
-    pub fn new() -> Option<Self> {
-        let init = std::time::Instant::now();
-        let (mut h, t) = raylib::init()
-            .msaa_4x()
-            .size(1280, 720)
-            .resizable()
-            .vsync()
-            .log_level(raylib::ffi::TraceLogLevel::LOG_FATAL)
-            .build();
-
-        eprintln!("[INFO]: Raylib initialized");
-
-        h.set_exit_key(None);
+    // takes references so only part of the model can be written out without cloning it, the paths
+    // inside of `root` get stored relative to it (see `Config::index_root`)
+    fn serialize<'a>(
+        self,
+        docs: impl Iterator<Item = (&'a String, &'a search_model::Document)>,
+        root: Option<&std::path::Path>,
+    ) -> Result<Vec<u8>, String> {
+        let docs: HashMap<_, _> = docs.map(|(p, d)| (index_key(root, p), d)).collect();
+        match self {
+            Self::Json => serde_json::to_vec(&docs).map_err(|e| e.to_string()),
+            Self::Binary => wincode::serialize(&docs).map_err(|e| e.to_string()),
+        }
+    }
 
-        let (document_base_dir, config_file, index_file) = Self::init_directories()?;
-        eprintln!("[INFO]: Directories initialized");
+    // `serialize` straight into the file
+    fn write<'a>(
+        self,
+        p: &std::path::Path,
+        docs: impl Iterator<Item = (&'a String, &'a search_model::Document)>,
+        root: Option<&std::path::Path>,
+    ) -> Result<(), String> {
+        std::fs::write(p, self.serialize(docs, root)?).map_err(|e| e.to_string())
+    }
 
-        let config = match Self::init_config(&document_base_dir, &config_file) {
-            Some(c) => c,
-            None => return None,
+    fn deserialize(
+        self,
+        bytes: &[u8],
+        root: Option<&std::path::Path>,
+    ) -> Result<HashMap<String, search_model::Document>, String> {
+        let docs: HashMap<String, search_model::Document> = match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string())?,
+            Self::Binary => wincode::deserialize(bytes).map_err(|e| e.to_string())?,
         };
+        Ok(docs
+            .into_iter()
+            .map(|(p, d)| (resolve_index_key(root, p), d))
+            .collect())
+    }
+}
 
-        eprintln!("[INFO]: Configuration (toml) initialized");
-
-        let model_begin = std::time::Instant::now();
-        let model = Self::init_model(&index_file, &config);
-        let model_end = model_begin.elapsed();
-
-        eprintln!("[INFO]: Search model initialized");
+// NOTE: Here we use serde (toml) since its a config file come on guys
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+struct Config {
+    document_directories: Vec<String>,
+    font_name: Option<String>,
+    theme: Theme,
+    // query that gets run as soon as the app starts
+    startup_query: Option<String>,
+    #[serde(default)]
+    index_modification_dates: bool,
+    max_depth: Option<usize>,
+    // exact paths of files that never get indexed, relative ones are relative to the document base
+    // directory just like `document_directories`
+    #[serde(default)]
+    excluded_files: Vec<String>,
+    // keeps unstemmed terms in the index so exact searches (<C-e>) work
+    #[serde(default)]
+    store_raw_terms: bool,
+    // keeps the case of the unstemmed terms so exact searches have to match it too
+    #[serde(default)]
+    case_sensitive: bool,
+    // keeps the position of every word so `NEAR/<n>` can rank documents by how close terms are
+    // and "quoted phrases" can match, on unless set
+    index_positions: Option<bool>,
+    // indexes xml/xhtml attribute values too, handy for `<meta>` summaries but noisy for most xml
+    #[serde(default)]
+    index_xml_attributes: bool,
+    // the notes on pdf annotations (comments, highlights) as a boosted field
+    #[serde(default)]
+    index_pdf_annotations: bool,
+    // the directory names and file names of the documents as a boosted field, `path:...` searches
+    // just them
+    #[serde(default)]
+    index_paths: bool,
+    // segments cjk text into bigrams, otherwise a whole sentence without spaces is one term
+    #[serde(default)]
+    cjk_bigrams: bool,
+    // longer words (base64 blobs, urls...) don't get indexed, 64 characters if unset, 0 for no limit
+    max_token_length: Option<usize>,
+    // lines of .txt files starting with one of these tags (e.g. "Title" for `Title: ...`) are
+    // indexed as boosted fields that can be searched with `title:...`
+    #[serde(default)]
+    field_tags: Vec<String>,
+    // a query stops after this many milliseconds (250 if unset) and shows what it found so far,
+    // 0 means no limit
+    query_timeout_ms: Option<u64>,
+    // "tf-idf" (the default) or "bm25", which doesn't reward long and repetitive documents as much
+    #[serde(default)]
+    ranking: Ranking,
+    // how fast more occurrences of a term stop counting with bm25 (1.2 if unset)
+    bm25_k1: Option<f64>,
+    // how much long documents get pushed down with bm25, 0-1 (0.75 if unset)
+    bm25_b: Option<f64>,
+    // whether term frequencies are relative to all terms of a document or just the distinct ones
+    // (tf-idf only)
+    #[serde(default)]
+    tf_normalization: search_model::TfNormalization,
+    // shows the score of each result, raw tf-idf or as a percentage comparable across queries
+    show_scores: Option<search_model::ScoreScale>,
+    #[serde(default)]
+    index_format: IndexFormat,
+    // extension -> command that opens it, `{path}` gets replaced with the document
+    #[serde(default)]
+    open_with: HashMap<String, String>,
+    // characters the query box takes before it ignores any more input (256 if unset)
+    max_query_length: Option<usize>,
+    // search while typing (the default) instead of only on <Enter>
+    live_search: Option<bool>,
+    // paths inside of this directory are stored relative to it in the index file, so an index
+    // synced to another machine works as long as the documents are laid out the same under its root
+    index_root: Option<String>,
+    // drop documents whose files got deleted when loading the index (the default)
+    prune_deleted: Option<bool>,
+    // rebuilds the index in the background every this many minutes, off if unset or 0
+    auto_reindex_minutes: Option<u64>,
+    // size multiplier for the ui controls, the display's scale if unset
+    ui_scale: Option<f32>,
+    // the contrast ratio the text needs against the highlight color, which gets darkened or
+    // lightened until it has it (3 if unset, 0 to keep the theme's color as it is)
+    min_highlight_contrast: Option<f32>,
+    // the "local search" title at the top (the default), without it the results get that space
+    show_title: Option<bool>,
+    // new results scroll back up smoothly instead of jumping to the top
+    #[serde(default)]
+    smooth_scroll_to_top: bool,
+    // shows the text around the first match under each result, read from the files on demand
+    #[serde(default)]
+    snippets: bool,
+    // how many words a snippet shows around the match (12 if unset), half of them before it
+    snippet_words: Option<usize>,
+    // how many documents the extracted text is kept around for after previewing them (16 if unset)
+    text_cache_size: Option<usize>,
+    // prebuilt index files (e.g. synced from other machines) that get searched along with ours
+    #[serde(default)]
+    merged_indexes: Vec<String>,
+    // layout the app starts with, <C-l> switches between them
+    #[serde(default)]
+    layout: Layout,
+    // never reindexes or writes the index, for searching an index someone else maintains
+    #[serde(default)]
+    read_only: bool,
+    // what gets listed while the query is empty
+    #[serde(default)]
+    empty_query: EmptyQuery,
+    // start with the dense result list, <C-k> switches
+    #[serde(default)]
+    compact_results: bool,
+    // whether matches get shown by highlighting the query terms, tinting the whole row by its score
+    // or both
+    #[serde(default)]
+    result_highlight: ResultHighlight,
+    // system wide shortcut (e.g. "super+space") that brings the window up, off if unset
+    global_hotkey: Option<String>,
+    // "error", "warn", "info" (the default), "debug", "trace" or "off"
+    log_level: Option<log::LevelFilter>,
+    // also appends the log to local-search.log next to the index
+    #[serde(default)]
+    log_to_file: bool,
+    // logs how many characters got extracted from each file while indexing, handy when writing a
+    // new `TextExtractor`
+    #[serde(default)]
+    log_extracted_chars: bool,
+    // document directories that came from `LOCAL_SEARCH_DIRS`, they're not written back
+    #[serde(skip)]
+    env_directories: Vec<String>,
+}
 
-        let font = if let Some(name) = &config.font_name {
-            let cache = rust_fontconfig::FcFontCache::build();
-            let mut trace = Vec::new();
-            let results = cache.query(
-                &rust_fontconfig::FcPattern {
-                    name: Some(name.clone()),
-                    ..Default::default()
-                },
-                &mut trace,
-            );
-            match results {
-                Some(r) => {
-                    let bytes = cache.get_font_bytes(&r.id).unwrap();
-                    h.load_font_from_memory(&t, ".ttf", &bytes, 64, None)
-                        .unwrap()
-                }
-                None => {
-                    eprintln!(
-                        "[WARN]: Failed to find font {}, falling back to built in font",
-                        &name
-                    );
-                    h.load_font_from_memory(&t, ".otf", FONT, 64, None).unwrap()
-                }
+impl Config {
+    // uses the `open_with` command for the extension if there is one, the OS default otherwise
+    fn open_document(&self, p: &std::path::Path) {
+        let command = p
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| self.open_with.get(&e.to_lowercase()));
+        let Some(command) = command else {
+            if let Err(e) = open::that(p) {
+                log::error!("Failed to open {}: {e}", p.display());
             }
-        } else {
-            h.load_font_from_memory(&t, ".otf", FONT, 64, None).unwrap()
+            return;
         };
+        let path = p.to_string_lossy();
+        let mut parts = command.split_whitespace();
+        let Some(program) = parts.next() else {
+            log::error!("Empty open_with command for {}", p.display());
+            return;
+        };
+        let mut args: Vec<String> = parts.map(|a| a.replace("{path}", &path)).collect();
+        if !command.contains("{path}") {
+            args.push(path.to_string());
+        }
+        if let Err(e) = std::process::Command::new(program).args(&args).spawn() {
+            log::error!("Failed to run `{command}` for {}: {e}", p.display());
+        }
+    }
 
-        eprintln!("[INFO]: Font loaded");
-
-        let colors = config.theme.get_all_colors();
-
-        let bg_color = colors.background_color.into_raylib();
-        let fg_color = colors.foreground_color.into_raylib();
-        let idle_color = colors.idle_color.into_raylib();
-        let hover_color = colors.hovered_color.into_raylib();
-        let click_color = colors.clicked_color.into_raylib();
+    fn index_root(&self) -> Option<&std::path::Path> {
+        self.index_root.as_deref().map(std::path::Path::new)
+    }
 
-        eprintln!("[INFO]: Theme initialized");
+    fn prune_deleted(&self) -> bool {
+        self.prune_deleted.unwrap_or(true)
+    }
 
-        eprintln!("[INFO]: All state is hopefully ready to go");
+    fn live_search(&self) -> bool {
+        self.live_search.unwrap_or(true)
+    }
 
-        Some(Self {
-            raylib_thread: t,
-            raylib_handle: h,
-            font,
-            bg_color,
-            fg_color,
-            idle_color,
-            hover_color,
-            click_color,
-            doc_offset: 0.0,
-            docs: vec![],
-            model,
-            query: String::new(),
-            query_box_selected: false,
-            scroll_velocity: raylib::math::Vector2::zero(),
-            conf: config,
-            index_file,
-            boot_time: init.elapsed(),
-            boot_index_time: model_end,
-            update_time: std::time::Duration::from_secs(0),
-            draw_time: std::time::Duration::from_secs(0),
-            last_query_time: std::time::Duration::from_secs(0),
-            reindex_time: std::time::Duration::from_secs(0),
-            display_profile_data: false,
-        })
+    fn auto_reindex_interval(&self) -> Option<std::time::Duration> {
+        self.auto_reindex_minutes
+            .filter(|m| *m > 0 && !self.read_only)
+            .map(|m| std::time::Duration::from_secs(m * 60))
     }
 
-    // only reindexes the files (does not refresh the config)
-    fn refresh_model(&mut self) {
-        self.model.clear();
-        let reindex = std::time::Instant::now();
-        for p in &self.conf.document_directories {
-            let m = search_model::analyze_dir(&std::path::PathBuf::from(p)).unwrap();
-            m.into_iter().for_each(|(k, v)| {
-                self.model.insert(k, v);
-            });
+    fn max_token_length(&self) -> Option<usize> {
+        match self.max_token_length {
+            Some(0) => None,
+            max => Some(max.unwrap_or(64)),
         }
-        self.reindex_time = reindex.elapsed();
-        std::fs::write(&self.index_file, wincode::serialize(&self.model).unwrap()).unwrap();
     }
 
-    pub fn run(mut self) {
-        let label_text = "local search";
-        let label_size = self.font.measure_text(label_text, 64.0, 0.0);
+    fn min_highlight_contrast(&self) -> f32 {
+        self.min_highlight_contrast.unwrap_or(3.0)
+    }
+
+    fn show_title(&self) -> bool {
+        self.show_title.unwrap_or(true)
+    }
+
+    fn index_positions(&self) -> bool {
+        self.index_positions.unwrap_or(true)
+    }
+
+    fn max_query_length(&self) -> usize {
+        self.max_query_length.unwrap_or(256)
+    }
+
+    fn scoring(&self) -> search_model::Scoring {
+        match self.ranking {
+            Ranking::TfIdf => search_model::Scoring::TfIdf(self.tf_normalization),
+            Ranking::Bm25 => search_model::Scoring::Bm25 {
+                k1: self.bm25_k1.unwrap_or(search_model::BM25_K1),
+                b: self.bm25_b.unwrap_or(search_model::BM25_B).clamp(0.0, 1.0),
+            },
+        }
+    }
+
+    fn snippet_words(&self) -> usize {
+        self.snippet_words.unwrap_or(12)
+    }
+
+    fn text_cache_size(&self) -> usize {
+        self.text_cache_size.unwrap_or(16)
+    }
+
+    fn query_budget(&self) -> Option<std::time::Duration> {
+        match self.query_timeout_ms.unwrap_or(250) {
+            0 => None,
+            ms => Some(std::time::Duration::from_millis(ms)),
+        }
+    }
+
+    fn index_options(&self) -> search_model::IndexOptions {
+        search_model::IndexOptions {
+            index_dates: self.index_modification_dates,
+            max_depth: self.max_depth,
+            excluded_files: self
+                .excluded_files
+                .iter()
+                .map(std::path::PathBuf::from)
+                .collect(),
+            store_raw_terms: self.store_raw_terms,
+            case_sensitive: self.case_sensitive,
+            store_positions: self.index_positions(),
+            cjk_bigrams: self.cjk_bigrams,
+            xml_attributes: self.index_xml_attributes,
+            pdf_annotations: self.index_pdf_annotations,
+            field_tags: self.field_tags.iter().map(|t| t.to_lowercase()).collect(),
+            max_token_length: self.max_token_length(),
+            path_terms: self.index_paths,
+            log_extracted_chars: self.log_extracted_chars,
+        }
+    }
+}
+
+// NOTE: Timestamps are stored as unix seconds so the file stays human readable
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+struct IndexState {
+    last_indexed: HashMap<String, u64>,
+}
+
+// state files are optional, so a missing or broken one just starts out empty
+fn load_state<T: serde::de::DeserializeOwned + Default>(p: &std::path::Path, what: &str) -> T {
+    if !p.exists() {
+        return T::default();
+    }
+    let parsed = std::fs::read_to_string(p)
+        .map_err(|e| e.to_string())
+        .and_then(|s| toml::de::from_str(&s).map_err(|e| e.to_string()));
+    match parsed {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("Failed to load {what} {}: {e}", p.display());
+            T::default()
+        }
+    }
+}
+
+// whether any file in the directory was modified after the unix timestamp
+fn changed_since(dir: &str, secs: u64) -> bool {
+    // comparing whole seconds since that's the precision of the stored timestamp
+    search_model::newest_modification(std::path::Path::new(dir)).is_some_and(|newest| {
+        newest
+            .duration_since(std::time::UNIX_EPOCH)
+            .is_ok_and(|t| t.as_secs() > secs)
+    })
+}
+
+fn save_state<T: Serialize>(state: &T, p: &std::path::Path, what: &str) {
+    let s = match toml::ser::to_string_pretty(state) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Failed to serialize {what}: {e}");
+            return;
+        }
+    };
+    if let Err(e) = std::fs::write(p, s) {
+        log::error!("Failed to write {what} to {}: {e}", p.display());
+    }
+}
+
+impl IndexState {
+    fn load(p: &std::path::Path) -> Self {
+        load_state(p, "index state")
+    }
+
+    fn save(&self, p: &std::path::Path) {
+        save_state(self, p, "index state");
+    }
+
+    fn mark_indexed(&mut self, dirs: &[String]) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        for d in dirs {
+            self.last_indexed.insert(d.clone(), now);
+        }
+    }
+
+    // logs when every directory was last indexed and warns about the ones that changed since,
+    // returns whether any of them did
+    fn report_staleness(&self, dirs: &[String]) -> bool {
+        let mut stale = false;
+        for d in dirs {
+            let Some(&secs) = self.last_indexed.get(d) else {
+                log::warn!("{d} has never been indexed, press <r> to reindex");
+                stale = true;
+                continue;
+            };
+            let indexed_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            let age = std::time::SystemTime::now()
+                .duration_since(indexed_at)
+                .unwrap_or_default();
+            log::info!("{d} was last indexed {} ago", format_age(age));
+            if changed_since(d, secs) {
+                log::warn!("{d} has changed since it was last indexed, press <r> to reindex");
+                stale = true;
+            }
+        }
+        stale
+    }
+
+    // same as `report_staleness` without the logging, for checking again every now and then
+    fn any_stale(&self, dirs: &[String]) -> bool {
+        dirs.iter().any(|d| match self.last_indexed.get(d) {
+            Some(&secs) => changed_since(d, secs),
+            None => true,
+        })
+    }
+}
+
+// documents that always show up first, in the order they got pinned
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Pinned {
+    paths: Vec<String>,
+}
+
+impl Pinned {
+    fn load(p: &std::path::Path) -> Self {
+        load_state(p, "pinned documents")
+    }
+
+    fn save(&self, p: &std::path::Path) {
+        save_state(self, p, "pinned documents");
+    }
+
+    fn contains(&self, doc: &str) -> bool {
+        self.paths.iter().any(|p| p == doc)
+    }
+
+    // pins the document or unpins it if it already was
+    fn toggle(&mut self, doc: &str) {
+        if self.contains(doc) {
+            self.paths.retain(|p| p != doc);
+        } else {
+            self.paths.push(doc.to_string());
+        }
+    }
+}
+
+// the size and the position of the window when the app got closed, it opens the same way next time
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct WindowState {
+    size: Option<(i32, i32)>,
+    position: Option<(i32, i32)>,
+}
+
+impl WindowState {
+    fn load(p: &std::path::Path) -> Self {
+        load_state(p, "window state")
+    }
+
+    fn save(&self, p: &std::path::Path) {
+        save_state(self, p, "window state");
+    }
+}
+
+fn format_age(d: std::time::Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..60 => format!("{secs} seconds"),
+        60..3600 => format!("{} minutes", secs / 60),
+        3600..86400 => format!("{} hours", secs / 3600),
+        _ => format!("{} days", secs / 86400),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        1048576..1073741824 => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+        _ => format!("{:.1} GiB", bytes as f64 / 1073741824.0),
+    }
+}
+
+// how far (in pixels) the mouse or the result list can move between pressing and releasing the
+// button on a result for it to still count as a click that opens it
+const CLICK_SLOP: f32 = 8.0;
+
+// how long a result has to be hovered before its tooltip shows up
+const TOOLTIP_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+struct Tooltip {
+    // index into `App::docs`
+    doc: usize,
+    since: std::time::Instant,
+    text: String,
+}
+
+impl Tooltip {
+    fn new(
+        doc: usize,
+        path: &str,
+        model: &HashMap<String, search_model::Document>,
+        source: Option<&str>,
+    ) -> Self {
+        let mut lines = vec![path.to_string()];
+        if let Some(source) = source {
+            lines.push(format!("Index: {source}"));
+        }
+        // archive entries don't have their own file so the archive's size is shown
+        let (file, size_label) = match path.split_once(search_model::ARCHIVE_SEPARATOR) {
+            Some((archive, _)) => (archive, "Archive size"),
+            None => (path, "Size"),
+        };
+        if let Ok(m) = std::fs::metadata(file) {
+            lines.push(format!("{size_label}: {}", format_size(m.len())));
+        }
+        if let Some(d) = model.get(path) {
+            if d.modified() != 0 {
+                lines.push(format!(
+                    "Modified: {}",
+                    search_model::format_date(d.modified())
+                ));
+            }
+            lines.push(format!("Words: {}", d.word_count()));
+        }
+        Self {
+            doc,
+            since: std::time::Instant::now(),
+            text: lines.join("\n"),
+        }
+    }
+}
+
+// row height (relative to the search bar) and font size of the compact result list
+const COMPACT_ROW_SCALE: f32 = 0.6;
+const COMPACT_FONT_SIZE: f32 = 20.0;
+
+// rows with a snippet under the path are this much taller, the snippet text is this much smaller
+const SNIPPET_ROW_SCALE: f32 = 1.8;
+const SNIPPET_FONT_SCALE: f32 = 0.7;
+// the files get read again for the snippets, a few per frame keeps the ui responsive while they
+// come in
+const SNIPPETS_PER_FRAME: usize = 2;
+
+// how often the document directories get checked for changes the index doesn't have yet
+const STALE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const STALE_HINT: &str = "index may be out of date";
+// how often a reindex reports how far it got
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+// size of the settings button at a ui scale of 1
+const SETTINGS_BUTTON_SIZE: f32 = 96.0;
+
+// the settings button sits in the top left corner, scaled with the ui but never bigger than a
+// sixth of the window so it doesn't cover the rest on small windows
+fn settings_button_rect(w_w: f32, w_h: f32, scale: f32) -> raylib::math::Rectangle {
+    let size = (SETTINGS_BUTTON_SIZE * scale).min(w_w.min(w_h) / 6.0);
+    let margin = size / 8.0;
+    raylib::math::Rectangle::new(margin, margin, size, size)
+}
+
+// focuses the query box, it's never typed into the query
+const FOCUS_KEY: raylib::consts::KeyboardKey = raylib::consts::KeyboardKey::KEY_SLASH;
+const FOCUS_CHAR: char = '/';
+
+// what the help overlay (<F1> or <?>) lists, the keys are handled in `App::run` so this has to be
+// kept in sync with it (and the README)
+const KEYBINDS: &[(&str, &str)] = &[
+    ("<F1> / <?>", "show/hide this help"),
+    (
+        "</>, <Tab>",
+        "focus the query box (<Tab> again or <Esc> unfocuses it)",
+    ),
+    (
+        "<Enter>",
+        "search (or open the selected result when the query box isn't focused)",
+    ),
+    ("<Up> / <Down>", "select a result"),
+    ("<1>-<9>", "open the first to ninth result"),
+    ("<C-p>", "pin/unpin the selected result"),
+    ("<r>", "reindex the files that changed"),
+    ("<S-r>", "reindex all files"),
+    ("<C-r>", "reindex the file of the selected result"),
+    ("<C-e>", "toggle exact (unstemmed) search"),
+    ("<C-l>", "switch between the list and split layout"),
+    (
+        "<C-k>",
+        "switch between the compact and regular result list",
+    ),
+    ("<C-S-c>", "copy the paths of all results"),
+    ("<C-d>", "show debug info"),
+];
+// these do nothing with `read_only = true` so they're left out of the help then
+const REINDEX_KEYBINDS: &[&str] = &["<r>", "<S-r>", "<C-r>"];
+// <C-e> only works with `store_raw_terms = true`
+const EXACT_KEYBIND: &str = "<C-e>";
+
+// how many documents get added to the list at a time while browsing with an empty query
+const BROWSE_PAGE_SIZE: usize = 100;
+
+// what the scores of documents opened in this session get multiplied by when ranking, so the ones
+// you keep going back to while researching float up
+const OPENED_BOOST: f64 = 1.25;
+
+// how many documents `EmptyQuery::Recent` lists
+const RECENT_DOCUMENTS: usize = 20;
+
+// only the start of a document is shown in the preview pane
+const PREVIEW_CHARS: usize = 8192;
+
+// text shown in the preview pane of the split layout
+struct Preview {
+    doc: String,
+    // `None` if the document couldn't be read
+    text: Option<String>,
+}
+
+impl Preview {
+    fn new(doc: &str, cache: &mut search_model::TextCache) -> Self {
+        let text = cache
+            .text(doc)
+            .map(|t| t.chars().take(PREVIEW_CHARS).collect());
+        Self {
+            doc: doc.to_string(),
+            text,
+        }
+    }
+}
+
+fn score_label(scale: search_model::ScoreScale, score: f64) -> String {
+    match scale {
+        search_model::ScoreScale::Raw => format!("{score:.3}"),
+        search_model::ScoreScale::Percent => format!("{score:.0}%"),
+    }
+}
+
+// word wraps text into at most `max_lines` lines of `width` characters, blank lines are dropped
+fn wrap_text(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_len = 0;
+        for word in paragraph.split_whitespace() {
+            // overlong words get cut, they'd run off the pane otherwise
+            let word: String = word.chars().take(width).collect();
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > width {
+                lines.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            if line_len > 0 {
+                line.push(' ');
+                line_len += 1;
+            }
+            line.push_str(&word);
+            line_len += word_len;
+        }
+        if line_len > 0 {
+            lines.push(line);
+        }
+        if lines.len() >= max_lines {
+            lines.truncate(max_lines);
+            break;
+        }
+    }
+    lines
+}
+
+const FONT: &[u8] = include_bytes!("../assets/GeistMonoNerdFontMono-Regular.otf");
+// NOTE: Icons are nerd font glyphs from the bundled font, so they work even with a custom `font_name`
+const ICON_SETTINGS: &str = "\u{f013}";
+const ICON_PIN: &str = "\u{f08d}";
+const ICON_ADD: &str = "\u{f067}";
+const ICON_REMOVE: &str = "\u{f00d}";
+const ICON_REINDEX: &str = "\u{f021}";
+const ICON_FILE: &str = "\u{f016}";
+const ICON_FILE_PDF: &str = "\u{f1c1}";
+const ICON_FILE_CODE: &str = "\u{f1c9}";
+const ICON_FILE_EMAIL: &str = "\u{f0e0}";
+const ICON_FILE_SUBTITLE: &str = "\u{f20a}";
+// every glyph above, the icon font only gets loaded with these
+const ICONS: &str =
+    "\u{f013}\u{f08d}\u{f067}\u{f00d}\u{f021}\u{f016}\u{f1c1}\u{f1c9}\u{f0e0}\u{f20a}";
+
+fn file_type_icon(ft: Option<search_model::FileType>) -> &'static str {
+    match ft {
+        Some(search_model::FileType::Pdf) => ICON_FILE_PDF,
+        Some(search_model::FileType::Xml) => ICON_FILE_CODE,
+        Some(search_model::FileType::Email) => ICON_FILE_EMAIL,
+        Some(search_model::FileType::Subtitle) => ICON_FILE_SUBTITLE,
+        _ => ICON_FILE,
+    }
+}
+
+/// Absolute paths from the config are taken as they are, relative ones are relative to `base`
+fn resolve_config_path(base: &std::path::Path, p: &std::path::Path) -> std::path::PathBuf {
+    if p.is_absolute() {
+        p.to_path_buf()
+    } else {
+        base.join(p)
+    }
+}
+
+// the path a document is stored under in the index file, relative to `root` if it's inside of it
+// (archive entries too, `bundle.zip!/notes.txt` is just a path with an odd file name here)
+fn index_key(root: Option<&std::path::Path>, doc: &str) -> String {
+    match root.and_then(|r| std::path::Path::new(doc).strip_prefix(r).ok()) {
+        Some(rel) => rel.to_string_lossy().into_owned(),
+        None => doc.to_string(),
+    }
+}
+
+// the other way around, only relative paths are joined onto `root`
+fn resolve_index_key(root: Option<&std::path::Path>, key: String) -> String {
+    match root {
+        Some(r) if std::path::Path::new(&key).is_relative() => {
+            r.join(&key).to_string_lossy().into_owned()
+        }
+        _ => key,
+    }
+}
+
+// how far the row of the best result gets blended towards the highlight color, kept subtle so the
+// text stays readable
+const ROW_TINT_STRENGTH: f32 = 0.4;
+
+// `base` blended towards `tint` by `amount` (0-1)
+fn blend_color(
+    base: raylib::color::Color,
+    tint: raylib::color::Color,
+    amount: f32,
+) -> raylib::color::Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount.clamp(0.0, 1.0)) as u8;
+    raylib::color::Color::new(
+        mix(base.r, tint.r),
+        mix(base.g, tint.g),
+        mix(base.b, tint.b),
+        base.a,
+    )
+}
+
+// rows below the search bar (settings, suggestions) are laid out like the results
+fn row_rect(search_rect: raylib::math::Rectangle, i: usize) -> raylib::math::Rectangle {
+    let mut rect = search_rect;
+    rect.y += (i + 1) as f32 * rect.height * 1.1;
+    rect
+}
+
+// square button on the right end of a row
+fn row_button(row: raylib::math::Rectangle) -> raylib::math::Rectangle {
+    raylib::math::Rectangle::new(
+        row.x + row.width - row.height,
+        row.y,
+        row.height,
+        row.height,
+    )
+}
+
+// the next square button to the left of `row_button`
+fn second_row_button(row: raylib::math::Rectangle) -> raylib::math::Rectangle {
+    let mut button = row_button(row);
+    button.x -= button.width;
+    button
+}
+
+// the manager unregisters the hotkey when it's dropped, so it has to stay around
+type GlobalHotkey = (
+    global_hotkey::GlobalHotKeyManager,
+    global_hotkey::hotkey::HotKey,
+);
+
+// NOTE: Only works where the OS lets apps grab keys, on linux that means X11 (not wayland)
+fn register_global_hotkey(s: &str) -> Option<GlobalHotkey> {
+    let hotkey: global_hotkey::hotkey::HotKey = match s.parse() {
+        Ok(h) => h,
+        Err(e) => {
+            log::error!("Failed to parse global hotkey {s}: {e}");
+            return None;
+        }
+    };
+    let manager = match global_hotkey::GlobalHotKeyManager::new() {
+        Ok(m) => m,
+        Err(e) => {
+            log::error!("Global hotkeys aren't available: {e}");
+            return None;
+        }
+    };
+    if let Err(e) = manager.register(hotkey) {
+        log::error!("Failed to register global hotkey {s}: {e}");
+        return None;
+    }
+    log::info!("Registered global hotkey {s}");
+    Some((manager, hotkey))
+}
+
+// indexes every document directory (each one on its own threads), a directory that fails gets
+// logged and skipped instead of taking the others down with it. The documents of `previous` whose
+// files didn't change are reused instead of being read again, pass an empty one to start over
+fn index_directories(
+    conf: &Config,
+    mut previous: HashMap<String, search_model::Document>,
+) -> HashMap<String, search_model::Document> {
+    let mut model = HashMap::new();
+    for p in &conf.document_directories {
+        let dir = std::path::Path::new(p);
+        match search_model::analyze_dir_incremental(dir, &conf.index_options(), &mut previous) {
+            Ok(m) => model.extend(m),
+            // e.g. an unplugged drive, the other directories still get indexed
+            Err(e) => log::warn!("Skipping document directory, {e}"),
+        }
+    }
+    drop_duplicates(&mut model);
+    model
+}
+
+// files with the same content only show up once in the results
+fn drop_duplicates(model: &mut HashMap<String, search_model::Document>) {
+    let removed = search_model::remove_duplicates(model);
+    if removed > 0 {
+        log::info!("Skipped {removed} duplicate documents");
+    }
+}
+
+// drops characters that can't be in a query, runs of spaces and anything past the max length
+// NOTE: The case is kept for `QueryMode::CaseSensitive`, the other modes fold it when the terms get
+// normalized
+fn push_query_char(query: &mut String, c: char, max_length: usize) {
+    // any script, cjk queries get split into bigrams later on
+    if !(c.is_alphanumeric()
+        || matches!(
+            c,
+            ' ' | '-' | '^' | '.' | '*' | ':' | '<' | '>' | '=' | '"' | '_'
+        ))
+    {
+        return;
+    }
+    if c == ' ' && (query.is_empty() || query.ends_with(' ')) {
+        return;
+    }
+    if query.chars().count() >= max_length {
+        return;
+    }
+    query.push(c);
+}
+
+// the words of the query terms that can show up in the text of a document, without the operators,
+// weights and excluded terms
+fn highlight_terms(terms: &[&str]) -> Vec<String> {
+    terms
+        .iter()
+        .map(|t| t.trim_matches('"'))
+        .filter(|t| {
+            !t.is_empty()
+                && search_model::parse_near_operator(t).is_none()
+                && search_model::parse_excluded_term(t).is_none()
+        })
+        .map(|t| {
+            let (t, _) = search_model::parse_weighted_term(t);
+            let (_, t) = search_model::parse_exact_term(search_model::parse_field_term(t).1);
+            t.to_string()
+        })
+        .collect()
+}
+
+// the terms of a query, with cjk runs split into bigrams like they got indexed
+fn split_query(query: &str, cjk_bigrams: bool) -> Vec<String> {
+    if cjk_bigrams {
+        query
+            .split_whitespace()
+            .flat_map(search_model::split_cjk_term)
+            .collect()
+    } else {
+        query.split_whitespace().map(str::to_string).collect()
+    }
+}
+
+// adds the documents of other prebuilt index files to the model and returns which index each of
+// them came from, documents that are already in the model win
+fn merge_indexes(
+    model: &mut HashMap<String, search_model::Document>,
+    paths: &[String],
+    // see `Config::index_root`, the other machines share it
+    root: Option<&std::path::Path>,
+) -> HashMap<String, String> {
+    let mut sources = HashMap::new();
+    for p in paths {
+        let p = std::path::Path::new(p);
+        let format = if p.extension().is_some_and(|e| e == "bin") {
+            IndexFormat::Binary
+        } else {
+            IndexFormat::Json
+        };
+        let loaded = std::fs::read(p)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| format.deserialize(&bytes, root));
+        let docs = match loaded {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("Failed to load merged index {}: {e}", p.display());
+                continue;
+            }
+        };
+        let source = p.file_stem().map_or_else(
+            || p.display().to_string(),
+            |s| s.to_string_lossy().to_string(),
+        );
+        let mut merged = 0;
+        for (doc, d) in docs {
+            if let std::collections::hash_map::Entry::Vacant(e) = model.entry(doc.clone()) {
+                e.insert(d);
+                sources.insert(doc, source.clone());
+                merged += 1;
+            }
+        }
+        log::info!("Merged {merged} documents from {}", p.display());
+    }
+    sources
+}
+
+// what the background load of the index ends up with
+struct LoadedModel {
+    model: HashMap<String, search_model::Document>,
+    // see `App::doc_sources`
+    sources: HashMap<String, String>,
+    // whether the index had to be built instead of loaded
+    rebuilt: bool,
+    took: std::time::Duration,
+}
+
+type ModelLoader = std::thread::JoinHandle<LoadedModel>;
+
+// a rebuild running in the background, setting `cancel` makes it stop after the files it's reading
+// right now
+struct Rebuild {
+    worker: ModelLoader,
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+// builds the index from scratch on another thread, without touching the one in use
+fn spawn_rebuild(conf: &Config) -> Rebuild {
+    let conf = conf.clone();
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let cancelled = cancel.clone();
+    let worker = std::thread::spawn(move || {
+        let begin = std::time::Instant::now();
+        let opts = conf.index_options();
+        let on_file = |_: &std::path::Path| {
+            if cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+                std::ops::ControlFlow::Break(())
+            } else {
+                std::ops::ControlFlow::Continue(())
+            }
+        };
+        let mut scan = search_model::DirScan::default();
+        for p in &conf.document_directories {
+            let dir = std::path::Path::new(p);
+            match search_model::scan_dir(dir, &opts, &HashMap::new(), &on_file) {
+                Ok(s) => scan.extend(s),
+                Err(e) => log::warn!("Skipping document directory, {e}"),
+            }
+        }
+        let mut model = scan.into_model(&mut HashMap::new());
+        drop_duplicates(&mut model);
+        let sources = merge_indexes(&mut model, &conf.merged_indexes, conf.index_root());
+        LoadedModel {
+            model,
+            sources,
+            rebuilt: true,
+            took: begin.elapsed(),
+        }
+    });
+    Rebuild { worker, cancel }
+}
+
+// how far a reindex got, the worker sends one every `PROGRESS_INTERVAL` and after the last file
+struct IndexProgress {
+    done: usize,
+    total: usize,
+    current: std::path::PathBuf,
+}
+
+// what a reindex in the background found, see `App::poll_refresh`
+struct RefreshedModel {
+    scan: search_model::DirScan,
+    // the documents of `Config::merged_indexes`, along with the index each came from
+    merged: HashMap<String, search_model::Document>,
+    sources: HashMap<String, String>,
+    took: std::time::Duration,
+}
+
+// a reindex started with <r> (or of a single document directory), the old model stays in use until
+// it's done
+struct Refresh {
+    worker: std::thread::JoinHandle<RefreshedModel>,
+    // same as `Rebuild::cancel`, for when the app gets closed before it's done
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    // the document directories getting reindexed, the documents of the others are kept as they are
+    dirs: Vec<String>,
+    progress: std::sync::mpsc::Receiver<IndexProgress>,
+    // `None` until the first file is done
+    last: Option<IndexProgress>,
+}
+
+impl Refresh {
+    // how much of the files are done, between 0 and 1
+    fn fraction(&self) -> f32 {
+        match &self.last {
+            Some(p) if p.total > 0 => (p.done as f32 / p.total as f32).min(1.0),
+            _ => 0.0,
+        }
+    }
+
+    fn status(&self) -> String {
+        match &self.last {
+            Some(p) => format!(
+                "indexing {}/{}: {}",
+                p.done,
+                p.total,
+                p.current
+                    .file_name()
+                    .map_or_else(|| p.current.to_string_lossy(), |n| n.to_string_lossy())
+            ),
+            None => "indexing...".to_string(),
+        }
+    }
+}
+
+// reads the files of `dirs` that don't match their stamp in `stamps` again on another thread, the
+// documents of the rest get moved over from the model in use once it's done
+fn spawn_refresh(
+    conf: &Config,
+    dirs: Vec<String>,
+    stamps: HashMap<String, search_model::FileStamp>,
+) -> Refresh {
+    let conf = conf.clone();
+    let (sender, progress) = std::sync::mpsc::channel();
+    let scanned = dirs.clone();
+    let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let cancelled = cancel.clone();
+    let worker = std::thread::spawn(move || {
+        let begin = std::time::Instant::now();
+        let opts = conf.index_options();
+        let total = scanned
+            .iter()
+            .map(|p| search_model::count_files(std::path::Path::new(p), &opts))
+            .sum();
+        let done = std::sync::atomic::AtomicUsize::new(0);
+        let last_sent = std::sync::Mutex::new(begin);
+        let on_file = |file: &std::path::Path| {
+            let done = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            // NOTE: One for every file would flood the channel on a directory of small files, the
+            // ui only shows the newest one anyway
+            let mut last_sent = last_sent.lock().unwrap();
+            if done >= total || last_sent.elapsed() >= PROGRESS_INTERVAL {
+                *last_sent = std::time::Instant::now();
+                // the receiver is gone when the app got closed meanwhile
+                let _ = sender.send(IndexProgress {
+                    done,
+                    total,
+                    current: file.to_path_buf(),
+                });
+            }
+            if cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+                std::ops::ControlFlow::Break(())
+            } else {
+                std::ops::ControlFlow::Continue(())
+            }
+        };
+        let mut scan = search_model::DirScan::default();
+        for p in &scanned {
+            match search_model::scan_dir(std::path::Path::new(p), &opts, &stamps, &on_file) {
+                Ok(s) => scan.extend(s),
+                Err(e) => log::warn!("Skipping document directory, {e}"),
+            }
+        }
+        let mut merged = HashMap::new();
+        let sources = merge_indexes(&mut merged, &conf.merged_indexes, conf.index_root());
+        RefreshedModel {
+            scan,
+            merged,
+            sources,
+            took: begin.elapsed(),
+        }
+    });
+    Refresh {
+        worker,
+        cancel,
+        dirs,
+        progress,
+        last: None,
+    }
+}
+
+struct App {
+    raylib_thread: raylib::prelude::RaylibThread,
+    raylib_handle: raylib::prelude::RaylibHandle,
+    font: raylib::text::Font,
+    icon_font: raylib::text::Font,
+    bg_color: raylib::color::Color,
+    fg_color: raylib::color::Color,
+    idle_color: raylib::color::Color,
+    hover_color: raylib::color::Color,
+    click_color: raylib::color::Color,
+    highlight_color: raylib::color::Color,
+    selection_color: raylib::color::Color,
+
+    model: HashMap<String, search_model::Document>,
+    // documents from `Config::merged_indexes` -> the name of the index they came from, they're
+    // searched like the rest but never written to our own index
+    doc_sources: HashMap<String, String>,
+    // set until the index is loaded, the window is usable before that but searching isn't
+    model_loader: Option<ModelLoader>,
+    // a rebuild started by `auto_reindex_minutes`, the old model stays in use until it's done
+    auto_reindex: Option<Rebuild>,
+    // called off rebuilds that are still finishing the files they were reading, they get dropped
+    // once they're done
+    cancelled_rebuilds: Vec<ModelLoader>,
+    // a reindex started with <r> (or the out of date hint) running in the background
+    refresh: Option<Refresh>,
+    // when the index was last built (or loaded), for `auto_reindex_minutes`
+    last_reindex: std::time::Instant,
+    // files changed since the index was built, shown as a hint to reindex
+    index_stale: bool,
+    // a scan of the document directories for `index_stale` running in the background
+    stale_check: Option<std::thread::JoinHandle<bool>>,
+    last_stale_check: std::time::Instant,
+    // a query submitted while the index was still loading, it runs once it's done
+    query_pending: bool,
+    // when the query was last typed into, for debouncing live search
+    query_edited_at: Option<std::time::Instant>,
+    terms: search_model::TermDictionary,
+    // set when the model changed without the index file getting written
+    index_dirty: bool,
+    docs: Vec<String>,
+    // scores of the ranked `docs`, empty when browsing or only filtering by date
+    scores: HashMap<String, f64>,
+    // the snippets of the results of the last query made so far, `None` if there's nothing to
+    // show for one
+    snippets: HashMap<String, Option<String>>,
+    // documents opened since the app started, they rank a bit higher (see `OPENED_BOOST`)
+    opened: HashSet<String>,
+    // documents that didn't fit on the pages of `docs` shown so far while browsing
+    browse_rest: Vec<String>,
+    query: String,
+    // terms of the query that produced `docs`, used for highlighting
+    last_terms: Vec<String>,
+    // "did you mean" query for when nothing was found
+    suggestion: Option<String>,
+    query_mode: search_model::QueryMode,
+    // set when the last query ran out of time
+    partial_results: bool,
+    // result picked with the arrow keys
+    selected_doc: Option<usize>,
+    // full path and metadata of the hovered result
+    tooltip: Option<Tooltip>,
+    // the result the mouse button went down on, along with where the mouse and `doc_offset` were
+    // at then, it gets opened if the button goes up on it without either moving
+    result_press: Option<(String, raylib::math::Vector2, f32)>,
+    layout: Layout,
+    preview: Option<Preview>,
+    text_cache: search_model::TextCache,
+    // smaller rows so more results fit on screen
+    compact: bool,
+    pinned: Pinned,
+    pinned_file: std::path::PathBuf,
+
+    query_box_selected: bool,
+
+    settings_open: bool,
+    // whether results from the document directory with the same index get shown
+    searched_directories: Vec<bool>,
+    // path typed into the settings panel to add as a document directory
+    new_directory: String,
+    new_directory_selected: bool,
+
+    scroll_velocity: raylib::math::Vector2,
+    doc_offset: f32,
+    // set while easing back to the top after a new query
+    scrolling_to_top: bool,
+
+    conf: Config,
+    global_hotkey: Option<GlobalHotkey>,
+
+    display_profile_data: bool,
+    // the keybind list drawn over everything
+    help_open: bool,
+
+    document_base_dir: std::path::PathBuf,
+    config_file: std::path::PathBuf,
+    index_file: std::path::PathBuf,
+    index_state_file: std::path::PathBuf,
+    index_state: IndexState,
+    window_state_file: std::path::PathBuf,
+    boot_time: std::time::Duration,
+    boot_index_time: std::time::Duration,
+    update_time: std::time::Duration,
+    draw_time: std::time::Duration,
+    last_query_time: std::time::Duration,
+    reindex_time: std::time::Duration,
+}
+
+impl App {
+    fn init_directories() -> Option<(std::path::PathBuf, std::path::PathBuf, std::path::PathBuf)> {
+        let app_dirs = match platform_dirs::AppDirs::new(Some("local-search"), false) {
+            Some(dirs) => dirs,
+            None => {
+                log::error!("Failed to get app directories (for config, state)");
+                return None;
+            }
+        };
+        let mut document_base_dir = match platform_dirs::UserDirs::new() {
+            Some(d) => d.document_dir,
+            None => {
+                log::error!("Failed to get user directories (for document storage)");
+                return None;
+            }
+        };
+        document_base_dir.push("local-search");
+
+        let config_file = app_dirs.config_dir.join("config.toml");
+        let index_file = app_dirs.state_dir.join("index.bin");
+        if !app_dirs.config_dir.exists() {
+            log::info!(
+                "Config directory {} does not exist, creating it...",
+                &app_dirs.config_dir.display()
+            );
+            match std::fs::create_dir_all(&app_dirs.config_dir) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!(
+                        "Failed to create config dir {}: {e}",
+                        app_dirs.config_dir.display()
+                    );
+                    return None;
+                }
+            };
+        }
+        if !app_dirs.state_dir.exists() {
+            log::info!(
+                "State directory {} does not exist, creating it...",
+                &app_dirs.state_dir.display()
+            );
+            match std::fs::create_dir_all(&app_dirs.state_dir) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!(
+                        "Failed to create state dir {}: {e}",
+                        app_dirs.state_dir.display()
+                    );
+                    return None;
+                }
+            };
+        }
+        if !document_base_dir.exists() {
+            log::info!(
+                "Document directory {} does not exist, creating it...",
+                &document_base_dir.display()
+            );
+            std::fs::create_dir_all(&document_base_dir).unwrap();
+            match std::fs::create_dir_all(&document_base_dir) {
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!(
+                        "Failed to create document storage dir {}: {e}",
+                        document_base_dir.display()
+                    );
+                    return None;
+                }
+            };
+        }
+        Some((document_base_dir, config_file, index_file))
+    }
+
+    // directories, config and logging, everything needed before the index can be loaded
+    fn init_settings() -> Option<(
+        std::path::PathBuf,
+        std::path::PathBuf,
+        std::path::PathBuf,
+        Config,
+    )> {
+        let (document_base_dir, config_file, index_file) = Self::init_directories()?;
+        log::info!("Directories initialized");
+
+        let config = Self::init_config(&document_base_dir, &config_file)?;
+        let log_file = index_file.with_file_name("local-search.log");
+        logging::configure(
+            config.log_level,
+            config.log_to_file.then_some(log_file.as_path()),
+        );
+
+        log::info!("Configuration (toml) initialized");
+
+        // every format has its own file so switching formats just builds a new index
+        let index_file = index_file.with_file_name(config.index_format.file_name());
+        Some((document_base_dir, config_file, index_file, config))
+    }
+
+    fn init_config(
+        document_base_dir: &std::path::Path,
+        config_file: &std::path::Path,
+    ) -> Option<Config> {
+        let mut config = Config::default();
+        config
+            .document_directories
+            .push(document_base_dir.to_string_lossy().to_string());
+        if config_file.exists() {
+            let conf_file_content = match std::fs::read_to_string(config_file) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::error!("Failed to read config file {}: {e}", config_file.display());
+                    return None;
+                }
+            };
+            config = match toml::de::from_str(&conf_file_content) {
+                Ok(c) => c,
+                Err(e) => {
+                    log::error!("Failed to parse config: {e}");
+                    return None;
+                }
+            };
+            for p in config
+                .document_directories
+                .iter_mut()
+                .chain(&mut config.excluded_files)
+                .chain(&mut config.merged_indexes)
+                .chain(&mut config.index_root)
+            {
+                let np = match std::path::PathBuf::from_str(p) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        log::error!("Failed to parse path string {p}: {e}");
+                        return None;
+                    }
+                };
+                *p = resolve_config_path(document_base_dir, &np)
+                    .to_string_lossy()
+                    .to_string();
+            }
+        } else {
+            match std::fs::write(
+                config_file,
+                match toml::ser::to_string_pretty(&config) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        log::error!("Failed to serialize {config:?}: {e}");
+                        return None;
+                    }
+                },
+            ) {
+                Ok(()) => {}
+                Err(e) => {
+                    log::error!("Failed to write config to {}: {e}", config_file.display());
+                    return None;
+                }
+            };
+        }
+        if let Some(dirs) = std::env::var_os("LOCAL_SEARCH_DIRS") {
+            for p in std::env::split_paths(&dirs) {
+                if p.as_os_str().is_empty() {
+                    continue;
+                }
+                let p = resolve_config_path(document_base_dir, &p)
+                    .to_string_lossy()
+                    .to_string();
+                if !config.document_directories.contains(&p) {
+                    log::info!("Added document directory {p} from LOCAL_SEARCH_DIRS");
+                    config.document_directories.push(p.clone());
+                    config.env_directories.push(p);
+                }
+            }
+        }
+        Some(config)
+    }
+
+    // the second value is set when the index had to be (re)built instead of loaded
+    fn init_model(
+        index_file: &std::path::Path,
+        conf: &Config,
+    ) -> (HashMap<String, search_model::Document>, bool) {
+        if !index_file.exists()
+            && let Some(model) = Self::migrate_json_index(index_file, conf)
+        {
+            return (model, false);
+        }
+        if index_file.exists() {
+            let loaded = std::fs::read(index_file)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| conf.index_format.deserialize(&bytes, conf.index_root()));
+            match loaded {
+                Ok(mut model) => {
+                    if conf.prune_deleted() {
+                        let removed = search_model::remove_missing(&mut model);
+                        if removed > 0 {
+                            log::info!("Removed {removed} deleted documents from the index");
+                            // the pruned model still gets used, it's written again next time
+                            if !conf.read_only
+                                && let Err(e) = conf.index_format.write(
+                                    index_file,
+                                    model.iter(),
+                                    conf.index_root(),
+                                )
+                            {
+                                log::error!(
+                                    "Failed to write the pruned index to {}: {e}",
+                                    index_file.display()
+                                );
+                            }
+                        }
+                    }
+                    return (model, false);
+                }
+                // NOTE: a corrupt index or one from an older version shouldn't brick the app, the
+                // documents are still there so it can just be rebuilt
+                Err(e) => log::error!(
+                    "Failed to load index {}: {e}, rebuilding it",
+                    index_file.display()
+                ),
+            }
+        }
+        let model = index_directories(conf, HashMap::new());
+        if conf.read_only {
+            log::warn!(
+                "No usable index at {}, the one built now isn't saved since the index is read only",
+                index_file.display()
+            );
+        } else if let Err(e) = conf
+            .index_format
+            .write(index_file, model.iter(), conf.index_root())
+        {
+            // it's still usable until the app gets closed
+            log::error!("Failed to write the index to {}: {e}", index_file.display());
+        }
+        (model, true)
+    }
+
+    // NOTE: The index used to be json by default, an old index.json gets converted into the binary
+    // index once instead of rebuilding everything
+    fn migrate_json_index(
+        index_file: &std::path::Path,
+        conf: &Config,
+    ) -> Option<HashMap<String, search_model::Document>> {
+        if !matches!(conf.index_format, IndexFormat::Binary) {
+            return None;
+        }
+        let json_file = index_file.with_file_name(IndexFormat::Json.file_name());
+        if !json_file.exists() {
+            return None;
+        }
+        let loaded = std::fs::read(&json_file)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| IndexFormat::Json.deserialize(&bytes, conf.index_root()));
+        let mut model = match loaded {
+            Ok(m) => m,
+            Err(e) => {
+                log::error!("Failed to load the old index {}: {e}", json_file.display());
+                return None;
+            }
+        };
+        // like a loaded index, so the deleted files don't make it into the new one
+        if conf.prune_deleted() {
+            let removed = search_model::remove_missing(&mut model);
+            if removed > 0 {
+                log::info!("Removed {removed} deleted documents from the old index");
+            }
+        }
+        // it's still searchable, just not converted
+        if conf.read_only {
+            log::warn!(
+                "Using the old index {} as it is since the index is read only",
+                json_file.display()
+            );
+            return Some(model);
+        }
+        if let Err(e) = IndexFormat::Binary.write(index_file, model.iter(), conf.index_root()) {
+            log::error!("Failed to write {}: {e}", index_file.display());
+            return Some(model);
+        }
+        log::info!(
+            "Converted the old index {} to {}",
+            json_file.display(),
+            index_file.display()
+        );
+        if let Err(e) = std::fs::remove_file(&json_file) {
+            log::warn!(
+                "Failed to remove the old index {}: {e}",
+                json_file.display()
+            );
+        }
+        Some(model)
+    }
+
+    pub fn new() -> Option<Self> {
+        let init = std::time::Instant::now();
+        let (mut h, t) = raylib::init()
+            .msaa_4x()
+            .size(1280, 720)
+            .resizable()
+            .vsync()
+            .log_level(raylib::ffi::TraceLogLevel::LOG_FATAL)
+            .build();
+
+        log::info!("Raylib initialized");
+
+        h.set_exit_key(None);
+
+        let (document_base_dir, config_file, index_file, config) = Self::init_settings()?;
+
+        let index_state_file = index_file.with_file_name("index_state.toml");
+        let pinned_file = index_file.with_file_name("pinned.toml");
+        let pinned = Pinned::load(&pinned_file);
+        let index_state = IndexState::load(&index_state_file);
+        let window_state_file = index_file.with_file_name("window.toml");
+        let window_state = WindowState::load(&window_state_file);
+        if let Some((width, height)) = window_state.size
+            && width > 0
+            && height > 0
+        {
+            h.set_window_size(width, height);
+        }
+        if let Some((x, y)) = window_state.position {
+            h.set_window_position(x, y);
+        }
+        // walking the document directories takes a while, `poll_stale_check` picks it up once done
+        let stale_check = (!config.read_only).then(|| {
+            let (state, dirs) = (index_state.clone(), config.document_directories.clone());
+            std::thread::spawn(move || state.report_staleness(&dirs))
+        });
+
+        // NOTE: Big indexes take a few seconds to load, doing it in the background lets the
+        // window show up right away
+        let model_loader = {
+            let (index_file, config) = (index_file.clone(), config.clone());
+            std::thread::spawn(move || {
+                let model_begin = std::time::Instant::now();
+                let (mut model, rebuilt) = Self::init_model(&index_file, &config);
+                let sources =
+                    merge_indexes(&mut model, &config.merged_indexes, config.index_root());
+                LoadedModel {
+                    model,
+                    sources,
+                    rebuilt,
+                    took: model_begin.elapsed(),
+                }
+            })
+        };
+
+        let font_name = config.theme.font_name().or(config.font_name.as_deref());
+        let custom_font = font_name.and_then(|name| {
+            let cache = rust_fontconfig::FcFontCache::build();
+            let mut trace = Vec::new();
+            let results = cache.query(
+                &rust_fontconfig::FcPattern {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                },
+                &mut trace,
+            );
+            let Some(r) = results else {
+                log::warn!("Failed to find font {name}, falling back to built in font");
+                return None;
+            };
+            // fontconfig can report fonts that are broken or unreadable
+            let Some(bytes) = cache.get_font_bytes(&r.id) else {
+                log::warn!("Failed to read font {name}, falling back to built in font");
+                return None;
+            };
+            match h.load_font_from_memory(&t, ".ttf", &bytes, 64, None) {
+                Ok(f) => Some(f),
+                Err(e) => {
+                    log::warn!("Failed to load font {name}: {e}, falling back to built in font");
+                    None
+                }
+            }
+        });
+        let font = match custom_font {
+            Some(f) => f,
+            None => h.load_font_from_memory(&t, ".otf", FONT, 64, None).unwrap(),
+        };
+
+        let icon_font = h
+            .load_font_from_memory(&t, ".otf", FONT, 64, Some(ICONS))
+            .unwrap();
+
+        log::info!("Font loaded");
+
+        let colors = config.theme.get_all_colors();
+
+        let bg_color = colors.background_color.into_raylib();
+        let fg_color = colors.foreground_color.into_raylib();
+        let idle_color = colors.idle_color.into_raylib();
+        let hover_color = colors.hovered_color.into_raylib();
+        let click_color = colors.clicked_color.into_raylib();
+        let mut highlight = colors.highlight_color.unwrap_or(colors.clicked_color);
+        let min_contrast = config.min_highlight_contrast();
+        let contrast = highlight.contrast(colors.foreground_color);
+        if contrast < min_contrast {
+            let readable = highlight.readable_behind(colors.foreground_color, min_contrast);
+            log::warn!(
+                "Highlighted text is hard to read on {} ({contrast:.1}:1), using {} instead",
+                highlight.hex(),
+                readable.hex()
+            );
+            highlight = readable;
+        }
+        let highlight_color = highlight.into_raylib();
+        let selection_color = colors
+            .selection_color
+            .unwrap_or(colors.hovered_color)
+            .into_raylib();
+
+        log::info!("Theme initialized");
+
+        let global_hotkey = config
+            .global_hotkey
+            .as_deref()
+            .and_then(register_global_hotkey);
+
+        log::info!("All state is hopefully ready to go");
+
+        let mut app = Self {
+            raylib_thread: t,
+            raylib_handle: h,
+            font,
+            icon_font,
+            bg_color,
+            fg_color,
+            idle_color,
+            hover_color,
+            click_color,
+            highlight_color,
+            selection_color,
+            doc_offset: 0.0,
+            scrolling_to_top: false,
+            docs: vec![],
+            browse_rest: vec![],
+            terms: search_model::TermDictionary::default(),
+            model: HashMap::new(),
+            doc_sources: HashMap::new(),
+            model_loader: Some(model_loader),
+            auto_reindex: None,
+            cancelled_rebuilds: vec![],
+            refresh: None,
+            last_reindex: std::time::Instant::now(),
+            index_stale: false,
+            stale_check,
+            last_stale_check: std::time::Instant::now(),
+            query_pending: false,
+            query_edited_at: None,
+            index_dirty: false,
+            scores: HashMap::new(),
+            opened: HashSet::new(),
+            snippets: HashMap::new(),
+            query: String::new(),
+            last_terms: vec![],
+            suggestion: None,
+            query_mode: search_model::QueryMode::default(),
+            partial_results: false,
+            selected_doc: None,
+            tooltip: None,
+            result_press: None,
+            layout: config.layout,
+            compact: config.compact_results,
+            preview: None,
+            text_cache: search_model::TextCache::new(config.text_cache_size()),
+            pinned,
+            pinned_file,
+            query_box_selected: false,
+            settings_open: false,
+            new_directory: String::new(),
+            new_directory_selected: false,
+            searched_directories: vec![true; config.document_directories.len()],
+            scroll_velocity: raylib::math::Vector2::zero(),
+            conf: config,
+            global_hotkey,
+            document_base_dir,
+            config_file,
+            index_file,
+            index_state_file,
+            index_state,
+            window_state_file,
+            boot_time: init.elapsed(),
+            boot_index_time: std::time::Duration::from_secs(0),
+            update_time: std::time::Duration::from_secs(0),
+            draw_time: std::time::Duration::from_secs(0),
+            last_query_time: std::time::Duration::from_secs(0),
+            reindex_time: std::time::Duration::from_secs(0),
+            display_profile_data: false,
+            help_open: false,
+        };
+
+        if let Some(q) = app.conf.startup_query.clone() {
+            app.query = q;
+            app.run_query();
+            log::info!("Running startup query \"{}\"", app.query);
+        } else {
+            // the pinned documents show up right away, the rest once the index is loaded
+            app.docs = app.pinned.paths.clone();
+            app.run_query();
+        }
+
+        Some(app)
+    }
+
+    // `ui_scale` from the config, otherwise the scale of the display (e.g. 2 on hidpi screens)
+    fn ui_scale(&self) -> f32 {
+        self.conf
+            .ui_scale
+            .unwrap_or_else(|| self.raylib_handle.get_window_scale_dpi().x)
+    }
+
+    fn loading(&self) -> bool {
+        self.model_loader.is_some()
+    }
+
+    // whether the model is about to get replaced, changing it until then would get lost
+    fn indexing(&self) -> bool {
+        self.loading() || self.refresh.is_some()
+    }
+
+    // what to tell someone whose index came out empty (usually on the first start), `None` if
+    // there's something to search
+    fn empty_index_hint(&self) -> Option<String> {
+        if self.loading() || !self.model.is_empty() {
+            return None;
+        }
+        Some(match self.conf.document_directories.as_slice() {
+            [] => "No document directories yet.".to_string(),
+            dirs => format!("No indexable documents found in {}.", dirs.join(", ")),
+        })
+    }
+
+    // installs the model once the background load is done
+    fn poll_model_loader(&mut self) {
+        if !self.model_loader.as_ref().is_some_and(|l| l.is_finished()) {
+            return;
+        }
+        let Some(loader) = self.model_loader.take() else {
+            return;
+        };
+        let LoadedModel {
+            model,
+            sources,
+            rebuilt,
+            took,
+        } = loader.join().unwrap();
+        self.model = model;
+        self.doc_sources = sources;
+        self.terms = search_model::TermDictionary::build(&self.model);
+        self.boot_index_time = took;
+        if rebuilt {
+            self.index_state
+                .mark_indexed(&self.conf.document_directories);
+            self.index_state.save(&self.index_state_file);
+        }
+        log::info!("Search model initialized");
+        if let Some(hint) = self.empty_index_hint() {
+            log::warn!("{hint}");
+        }
+        if std::mem::take(&mut self.query_pending) {
+            self.run_query();
+        }
+    }
+
+    // starts a background rebuild every `auto_reindex_minutes` and swaps it in once it's done,
+    // queries keep running on the old model until then
+    fn poll_auto_reindex(&mut self) {
+        self.cancelled_rebuilds.retain(|w| !w.is_finished());
+        match &self.auto_reindex {
+            Some(r) if r.worker.is_finished() => {}
+            Some(_) => return,
+            None => {
+                if let Some(interval) = self.conf.auto_reindex_interval()
+                    && !self.indexing()
+                    && self.last_reindex.elapsed() >= interval
+                {
+                    log::info!("Reindexing in the background");
+                    self.auto_reindex = Some(spawn_rebuild(&self.conf));
+                }
+                return;
+            }
+        }
+        let Some(rebuild) = self.auto_reindex.take() else {
+            return;
+        };
+        self.last_reindex = std::time::Instant::now();
+        let Ok(LoadedModel {
+            model,
+            sources,
+            took,
+            ..
+        }) = rebuild.worker.join()
+        else {
+            log::error!("Background reindex failed");
+            return;
+        };
+        self.model = model;
+        self.doc_sources = sources;
+        self.terms = search_model::TermDictionary::build(&self.model);
+        self.reindex_time = took;
+        self.save_index();
+        self.index_state
+            .mark_indexed(&self.conf.document_directories);
+        self.index_state.save(&self.index_state_file);
+        self.mark_fresh();
+        log::info!("Background reindex done in {}ms", took.as_millis());
+    }
+
+    // calls off a running `auto_reindex_minutes` rebuild, whatever it would find is older than the
+    // change about to be made
+    // NOTE: It isn't waited for, the files it's reading right now can take a while (a big pdf) and
+    // the ui would hang until then
+    fn cancel_auto_reindex(&mut self) {
+        let Some(rebuild) = self.auto_reindex.take() else {
+            return;
+        };
+        rebuild
+            .cancel
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        self.cancelled_rebuilds.push(rebuild.worker);
+    }
+
+    // rescans the document directories every `STALE_CHECK_INTERVAL` in the background to find out
+    // if the index is out of date
+    fn poll_stale_check(&mut self) {
+        match &self.stale_check {
+            Some(c) if c.is_finished() => {}
+            Some(_) => return,
+            None => {
+                // nothing to do about it anyway
+                if !self.index_stale
+                    && !self.conf.read_only
+                    && !self.indexing()
+                    && self.auto_reindex.is_none()
+                    && self.last_stale_check.elapsed() >= STALE_CHECK_INTERVAL
+                {
+                    let (state, dirs) = (
+                        self.index_state.clone(),
+                        self.conf.document_directories.clone(),
+                    );
+                    self.stale_check = Some(std::thread::spawn(move || state.any_stale(&dirs)));
+                }
+                return;
+            }
+        }
+        if let Some(check) = self.stale_check.take() {
+            self.index_stale = check.join().unwrap_or(false);
+            self.last_stale_check = std::time::Instant::now();
+        }
+    }
+
+    // the index was just (re)built, a check started before that would be outdated
+    fn mark_fresh(&mut self) {
+        self.index_stale = false;
+        self.stale_check = None;
+        self.last_stale_check = std::time::Instant::now();
+    }
+
+    // raises the window and focuses the query box when the global hotkey got pressed
+    fn poll_global_hotkey(&mut self) {
+        let Some((_, hotkey)) = &self.global_hotkey else {
+            return;
+        };
+        let mut pressed = false;
+        while let Ok(e) = global_hotkey::GlobalHotKeyEvent::receiver().try_recv() {
+            pressed |= e.id() == hotkey.id() && e.state() == global_hotkey::HotKeyState::Pressed;
+        }
+        if !pressed {
+            return;
+        }
+        if self.raylib_handle.is_window_minimized() {
+            self.raylib_handle.restore_window();
+        }
+        self.raylib_handle.set_window_focused();
+        self.query_box_selected = true;
+    }
+
+    // only reindexes the files (does not refresh the config), `full` reads the unchanged ones again
+    // too, for when the index settings changed. It runs in the background, see `poll_refresh`
+    fn refresh_model(&mut self, full: bool) {
+        if self.indexing() {
+            log::warn!("The index is still being built, try again in a moment");
+            return;
+        }
+        self.cancel_auto_reindex();
+        // NOTE: Only the files that changed since get read again, which makes a refresh of a big
+        // directory where little changed quick
+        let stamps = if full {
+            HashMap::new()
+        } else {
+            search_model::file_stamps(&self.model)
+        };
+        log::info!("Reindexing in the background");
+        self.refresh = Some(spawn_refresh(
+            &self.conf,
+            self.conf.document_directories.clone(),
+            stamps,
+        ));
+    }
+
+    // keeps track of the progress of a reindex and swaps in the new model once it's done, queries
+    // keep running on the old one until then
+    fn poll_refresh(&mut self) {
+        let Some(refresh) = &mut self.refresh else {
+            return;
+        };
+        while let Ok(p) = refresh.progress.try_recv() {
+            refresh.last = Some(p);
+        }
+        if !refresh.worker.is_finished() {
+            return;
+        }
+        let Some(refresh) = self.refresh.take() else {
+            return;
+        };
+        let Ok(RefreshedModel {
+            scan,
+            merged,
+            mut sources,
+            took,
+        }) = refresh.worker.join()
+        else {
+            log::error!("Reindexing failed");
+            return;
+        };
+        let mut previous = std::mem::take(&mut self.model);
+        // they get merged in again below
+        previous.retain(|doc, _| !self.doc_sources.contains_key(doc));
+        self.model = scan.into_model(&mut previous);
+        // the documents of the directories that weren't reindexed, what's left of the others are
+        // deleted files
+        let reindexed = |doc: &str| {
+            let doc = std::path::Path::new(doc);
+            refresh.dirs.iter().any(|d| doc.starts_with(d))
+        };
+        self.model
+            .extend(previous.into_iter().filter(|(doc, _)| !reindexed(doc)));
+        drop_duplicates(&mut self.model);
+        // our own documents win over merged ones with the same path
+        for (doc, d) in merged {
+            match self.model.entry(doc) {
+                std::collections::hash_map::Entry::Occupied(e) => {
+                    sources.remove(e.key());
+                }
+                std::collections::hash_map::Entry::Vacant(e) => {
+                    e.insert(d);
+                }
+            }
+        }
+        self.doc_sources = sources;
+        self.terms = search_model::TermDictionary::build(&self.model);
+        self.reindex_time = took;
+        if refresh.dirs == self.conf.document_directories {
+            self.last_reindex = std::time::Instant::now();
+            self.mark_fresh();
+        }
+        self.save_index();
+        self.index_state.mark_indexed(&refresh.dirs);
+        self.index_state.save(&self.index_state_file);
+        log::info!("Reindex done in {}ms", took.as_millis());
+        self.run_query();
+    }
+
+    // reindexes just the file of the document (the whole archive for archive entries)
+    fn reindex_document(&mut self, doc: &str) {
+        // a running refresh would replace the model and lose it (it reads the file too anyway)
+        if self.indexing() {
+            log::warn!("The index is still being built, try again in a moment");
+            return;
+        }
+        if let Some(source) = self.doc_sources.get(doc) {
+            log::warn!("{doc} comes from the merged index {source}, it can't be reindexed here");
+            return;
+        }
+        let file = doc
+            .split_once(search_model::ARCHIVE_SEPARATOR)
+            .map_or(doc, |(a, _)| a);
+        self.cancel_auto_reindex();
+        let t = std::time::Instant::now();
+        let updated = search_model::update_document(
+            &mut self.model,
+            &mut self.terms,
+            std::path::Path::new(file),
+            &self.conf.index_options(),
+        );
+        if let Err(e) = updated {
+            log::error!("Failed to reindex {file}, {e}");
+            return;
+        }
+        self.reindex_time = t.elapsed();
+        // NOTE: Writing the whole index for every single file adds up, it's saved on exit instead
+        self.index_dirty = true;
+        log::info!("Reindexed {file}");
+    }
+
+    fn save_index(&mut self) {
+        self.index_dirty = false;
+        if self.conf.read_only {
+            return;
+        }
+        let written = self.conf.index_format.write(
+            &self.index_file,
+            self.model
+                .iter()
+                .filter(|(p, _)| !self.doc_sources.contains_key(*p)),
+            self.conf.index_root(),
+        );
+        if let Err(e) = written {
+            log::error!(
+                "Failed to write the index to {}: {e}",
+                self.index_file.display()
+            );
+            // so it gets tried again on exit
+            self.index_dirty = true;
+        }
+    }
+
+    fn save_config(&self) {
+        let mut conf = self.conf.clone();
+        conf.document_directories
+            .retain(|d| !conf.env_directories.contains(d));
+        let s = match toml::ser::to_string_pretty(&conf) {
+            Ok(s) => s,
+            Err(e) => {
+                log::error!("Failed to serialize config: {e}");
+                return;
+            }
+        };
+        if let Err(e) = std::fs::write(&self.config_file, s) {
+            log::error!(
+                "Failed to write config to {}: {e}",
+                self.config_file.display()
+            );
+        }
+    }
+
+    // adds the directory to the config and indexes just that directory
+    fn add_document_directory(&mut self, p: &str) {
+        if self.indexing() {
+            log::warn!("The index is still being built, try again in a moment");
+            return;
+        }
+        let dir = resolve_config_path(&self.document_base_dir, std::path::Path::new(p));
+        if !dir.is_dir() {
+            log::error!("{} is not a directory", dir.display());
+            return;
+        }
+        let dir = dir.to_string_lossy().to_string();
+        if self.conf.document_directories.contains(&dir) {
+            log::warn!("{dir} already is a document directory");
+            return;
+        }
+        self.cancel_auto_reindex();
+        let t = std::time::Instant::now();
+        let m =
+            match search_model::analyze_dir(std::path::Path::new(&dir), &self.conf.index_options())
+            {
+                Ok(m) => m,
+                Err(e) => {
+                    log::error!("Failed to index {dir}, {e}");
+                    return;
+                }
+            };
+        // our own documents replace merged ones with the same path
+        for doc in m.keys() {
+            self.doc_sources.remove(doc);
+        }
+        self.model.extend(m);
+        drop_duplicates(&mut self.model);
+        self.terms = search_model::TermDictionary::build(&self.model);
+        self.reindex_time = t.elapsed();
+        self.save_index();
+        self.index_state.mark_indexed(std::slice::from_ref(&dir));
+        self.index_state.save(&self.index_state_file);
+        log::info!("Added document directory {dir}");
+        self.conf.document_directories.push(dir);
+        self.searched_directories.push(true);
+        self.save_config();
+    }
+
+    // drops the directory from the config along with every document only it contained
+    fn remove_document_directory(&mut self, i: usize) {
+        if self.indexing() {
+            log::warn!("The index is still being built, try again in a moment");
+            return;
+        }
+        self.cancel_auto_reindex();
+        let dir = self.conf.document_directories.remove(i);
+        self.searched_directories.remove(i);
+        let remaining = &self.conf.document_directories;
+        self.model.retain(|doc, _| {
+            let doc = std::path::Path::new(doc);
+            !doc.starts_with(&dir) || remaining.iter().any(|d| doc.starts_with(d))
+        });
+        self.terms = search_model::TermDictionary::build(&self.model);
+        self.save_index();
+        self.index_state.last_indexed.remove(&dir);
+        self.index_state.save(&self.index_state_file);
+        log::info!("Removed document directory {dir}");
+        self.save_config();
+    }
+
+    // reindexes just one of the document directories in the background, the documents of the others
+    // stay as they are, see `poll_refresh`
+    fn reindex_document_directory(&mut self, i: usize) {
+        if self.indexing() {
+            log::warn!("The index is still being built, try again in a moment");
+            return;
+        }
+        self.cancel_auto_reindex();
+        let dir = self.conf.document_directories[i].clone();
+        log::info!("Reindexing {dir} in the background");
+        self.refresh = Some(spawn_refresh(&self.conf, vec![dir], HashMap::new()));
+    }
+
+    fn push_query_char(&mut self, c: char) {
+        push_query_char(&mut self.query, c, self.conf.max_query_length());
+    }
+
+    // the documents for when the query is empty, see `EmptyQuery`
+    fn browse_docs(&self) -> Vec<String> {
+        let newest_first = |docs: &mut Vec<(&String, &search_model::Document)>| {
+            docs.sort_by(|(p1, d1), (p2, d2)| d2.modified().cmp(&d1.modified()).then(p1.cmp(p2)));
+        };
+        match self.conf.empty_query {
+            EmptyQuery::Browse => {
+                let mut rest: Vec<_> = self
+                    .model
+                    .iter()
+                    .filter(|(p, _)| !self.pinned.contains(p))
+                    .collect();
+                newest_first(&mut rest);
+                let mut docs = self.pinned.paths.clone();
+                docs.extend(rest.into_iter().map(|(p, _)| p.clone()));
+                docs
+            }
+            EmptyQuery::Recent => {
+                // documents with an unknown modification time aren't recent
+                let mut docs: Vec<_> = self
+                    .model
+                    .iter()
+                    .filter(|(_, d)| d.modified() != 0)
+                    .collect();
+                newest_first(&mut docs);
+                docs.into_iter()
+                    .take(RECENT_DOCUMENTS)
+                    .map(|(p, _)| p.clone())
+                    .collect()
+            }
+            EmptyQuery::Nothing => vec![],
+        }
+    }
+
+    // makes the snippets of the results on screen that don't have one yet, a few at a time
+    fn load_visible_snippets(&mut self, list_rect: raylib::math::Rectangle, w_h: f32) {
+        let terms: Vec<&str> = self.last_terms.iter().map(String::as_str).collect();
+        let missing = self.docs.iter().enumerate().filter(|(i, doc)| {
+            let y = row_rect(list_rect, *i).y + self.doc_offset;
+            y < w_h && y + list_rect.height > 0.0 && !self.snippets.contains_key(*doc)
+        });
+        let mut loaded = vec![];
+        for (_, doc) in missing.take(SNIPPETS_PER_FRAME) {
+            // the lines of the text get joined into one
+            let snippet = self.text_cache.text(doc).and_then(|text| {
+                search_model::snippet(text, &terms, self.query_mode, self.conf.snippet_words())
+            });
+            loaded.push((doc.clone(), snippet));
+        }
+        self.snippets.extend(loaded);
+    }
+
+    // every result (not just the loaded ones while browsing) one per line, e.g. for a batch tool
+    fn copy_result_paths(&mut self) {
+        let paths: Vec<&str> = self
+            .docs
+            .iter()
+            .chain(&self.browse_rest)
+            .map(String::as_str)
+            .collect();
+        match self.raylib_handle.set_clipboard_text(&paths.join("\n")) {
+            Ok(()) => log::info!("Copied {} result paths", paths.len()),
+            Err(e) => log::error!("Failed to copy the result paths: {e}"),
+        }
+    }
+
+    // the next page of documents while browsing
+    fn show_more_docs(&mut self) {
+        let n = self.browse_rest.len().min(BROWSE_PAGE_SIZE);
+        self.docs.extend(self.browse_rest.drain(..n));
+    }
+
+    fn run_query(&mut self) {
+        if self.loading() {
+            self.query_pending = true;
+            return;
+        }
+        let query_terms = split_query(&self.query, self.conf.cjk_bigrams);
+        let terms: Vec<&str> = query_terms.iter().map(String::as_str).collect();
+        let t = std::time::Instant::now();
+        let budget = self.conf.query_budget();
+        let mut docs;
+        (docs, self.partial_results) = search_model::do_query(
+            &self.model,
+            &self.terms,
+            &terms,
+            self.query_mode,
+            budget,
+            self.conf.scoring(),
+            self.conf.show_scores.unwrap_or_default(),
+        );
+        // date only queries score everything 0
+        self.scores = docs.iter().filter(|(_, s)| *s > 0.0).cloned().collect();
+        // NOTE: Only the order changes, the shown scores stay the ones of the query
+        if !self.opened.is_empty() {
+            let boosted = |(p, s): &(String, f64)| {
+                if self.opened.contains(p) {
+                    s * OPENED_BOOST
+                } else {
+                    *s
+                }
+            };
+            docs.sort_by(|a, b| boosted(b).total_cmp(&boosted(a)));
+        }
+        self.docs = docs.into_iter().map(|(d, _)| d).collect();
+        if terms.is_empty() {
+            self.docs = self.browse_docs();
+        } else {
+            // stable, so the pinned results keep their ranking among each other
+            self.docs.sort_by_key(|d| !self.pinned.contains(d));
+        }
+        if self.partial_results
+            && let Some(budget) = budget
+        {
+            log::warn!(
+                "Query ran longer than {}ms, only showing partial results",
+                budget.as_millis()
+            );
+        }
+        if self.searched_directories.contains(&false) {
+            let dirs: Vec<_> = self
+                .conf
+                .document_directories
+                .iter()
+                .zip(&self.searched_directories)
+                .filter(|(_, searched)| **searched)
+                .map(|(d, _)| std::path::Path::new(d))
+                .collect();
+            self.docs.retain(|doc| {
+                dirs.iter()
+                    .any(|d| std::path::Path::new(doc).starts_with(d))
+            });
+        }
+        // NOTE: Browsing lists the whole index, so it gets shown a page at a time
+        self.browse_rest = if terms.is_empty() {
+            self.docs.split_off(self.docs.len().min(BROWSE_PAGE_SIZE))
+        } else {
+            vec![]
+        };
+        self.last_terms = highlight_terms(&terms);
+        self.suggestion = None;
+        self.snippets.clear();
+        if self.docs.is_empty() {
+            self.suggestion =
+                search_model::suggest_correction(&self.terms, &terms, self.query_mode);
+        }
+        self.last_query_time = t.elapsed();
+        if self.conf.smooth_scroll_to_top {
+            self.scrolling_to_top = true;
+        } else {
+            self.doc_offset = 0.0;
+        }
+        self.selected_doc = None;
+        self.tooltip = None;
+    }
+
+    pub fn run(mut self) {
+        let label_text = "local search";
+        let label_size = self.font.measure_text(label_text, 64.0, 0.0);
 
         while !self.raylib_handle.window_should_close() {
             let w_w = self.raylib_handle.get_screen_width();
             let w_h = self.raylib_handle.get_screen_height();
 
             let update_time = std::time::Instant::now();
+            self.poll_model_loader();
+            self.poll_auto_reindex();
+            self.poll_refresh();
+            self.poll_stale_check();
+            self.poll_global_hotkey();
 
+            let settings_button = settings_button_rect(w_w as f32, w_h as f32, self.ui_scale());
             let label_pos = raylib::math::Vector2::new(
                 (w_w as f32 / 2.0) - label_size.x / 2.0,
                 w_h as f32 / 32.0,
             );
-            let search_rect = raylib::math::Rectangle::new(
-                w_w as f32 / 64.0,
-                label_pos.y + label_size.y * 1.5 + w_h as f32 / 64.0,
-                w_w as f32 - (w_w as f32 / 32.0),
-                label_size.y * 0.75,
-            );
+            let search_rect = if self.conf.show_title() {
+                raylib::math::Rectangle::new(
+                    w_w as f32 / 64.0,
+                    label_pos.y + label_size.y * 1.5 + w_h as f32 / 64.0,
+                    w_w as f32 - (w_w as f32 / 32.0),
+                    label_size.y * 0.75,
+                )
+            } else {
+                // takes the top row next to the settings button instead
+                let x = settings_button.x * 2.0 + settings_button.width;
+                raylib::math::Rectangle::new(
+                    x,
+                    settings_button.y,
+                    w_w as f32 - x - w_w as f32 / 64.0,
+                    label_size.y * 0.75,
+                )
+            };
+            let (mut list_rect, preview_rect) = self.layout.split(search_rect, w_h as f32);
+            let font_size = if self.compact {
+                list_rect.height = search_rect.height * COMPACT_ROW_SCALE;
+                COMPACT_FONT_SIZE
+            } else {
+                32.0
+            };
+            let snippet_rows = self.conf.snippets && !self.last_terms.is_empty();
+            if snippet_rows {
+                list_rect.height *= SNIPPET_ROW_SCALE;
+            }
+            // the first row still starts right below the search bar
+            list_rect.y += (search_rect.height - list_rect.height) * 1.1;
             let mut search_color = self.idle_color;
             if search_rect.check_collision_point_rec(self.raylib_handle.get_mouse_position()) {
                 search_color = self.hover_color;
@@ -460,104 +2595,502 @@ impl App {
             {
                 self.query_box_selected = false;
             }
+            let shift_down = self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_SHIFT);
+            // `?` is never typed into the query so it works while the query box is focused too
+            if self
+                .raylib_handle
+                .is_key_pressed(raylib::consts::KeyboardKey::KEY_F1)
+                || shift_down && self.raylib_handle.is_key_pressed(FOCUS_KEY)
+            {
+                self.help_open = !self.help_open;
+            } else if self
+                .raylib_handle
+                .is_key_pressed(raylib::consts::KeyboardKey::KEY_ESCAPE)
+            {
+                self.help_open = false;
+            }
+            // pressed, not down, so holding them doesn't flip the focus every frame
+            if !self.new_directory_selected {
+                if self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_TAB)
+                {
+                    self.query_box_selected = !self.query_box_selected;
+                }
+                if self.raylib_handle.is_key_pressed(FOCUS_KEY) && !shift_down {
+                    self.query_box_selected = true;
+                }
+            }
+            if self.query_box_selected {
+                search_color = self.hover_color;
+            }
+
+            let wheel = self.raylib_handle.get_mouse_wheel_move_v().y;
+            self.scroll_velocity.y += wheel * 10000.0;
+            self.scroll_velocity.y /= 1.2;
+            self.doc_offset += self.scroll_velocity.y * self.raylib_handle.get_frame_time();
+            self.doc_offset = self.doc_offset.clamp(-f32::MAX, 0.0);
+            // scrolling by hand takes over from the animation
+            if wheel != 0.0 {
+                self.scrolling_to_top = false;
+            }
+            if self.scrolling_to_top {
+                self.doc_offset /= 1.2;
+                if self.doc_offset > -1.0 {
+                    self.doc_offset = 0.0;
+                    self.scrolling_to_top = false;
+                }
+            }
+
+            if self.query_box_selected {
+                let query_before = self.query.clone();
+                if (self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_BACKSPACE)
+                    || self
+                        .raylib_handle
+                        .is_key_pressed_repeat(raylib::consts::KeyboardKey::KEY_BACKSPACE))
+                    && !self.query.is_empty()
+                {
+                    self.query.pop();
+                }
+
+                // NOTE: chars instead of keys so shifted symbols like `^` can be typed
+                while let Some(c) = self.raylib_handle.get_char_pressed() {
+                    // the `/` that focused the box shows up here in the same frame
+                    if c == FOCUS_CHAR {
+                        continue;
+                    }
+                    self.push_query_char(c);
+                }
+                if self.query != query_before {
+                    self.query_edited_at = Some(std::time::Instant::now());
+                }
+
+                if self
+                    .raylib_handle
+                    .is_key_down(raylib::consts::KeyboardKey::KEY_ENTER)
+                {
+                    self.query_edited_at = None;
+                    self.run_query();
+                }
+            }
+
+            // NOTE: Waits for a short pause in typing so every keystroke doesn't run a query
+            if self.conf.live_search()
+                && self
+                    .query_edited_at
+                    .is_some_and(|t| t.elapsed() > std::time::Duration::from_millis(150))
+            {
+                self.query_edited_at = None;
+                self.run_query();
+            }
+
+            let ctrl_down = self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL);
+            if self
+                .raylib_handle
+                .is_key_pressed(raylib::consts::KeyboardKey::KEY_R)
+                && !self.loading()
+                && !self.conf.read_only
+            {
+                if ctrl_down {
+                    if let Some(doc) = self.selected_doc.and_then(|i| self.docs.get(i)).cloned() {
+                        self.reindex_document(&doc);
+                        self.run_query();
+                    }
+                } else if !self.query_box_selected && !self.new_directory_selected {
+                    self.refresh_model(
+                        self.raylib_handle
+                            .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_SHIFT),
+                    );
+                }
+            }
+
+            if self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                && self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_D)
+            {
+                self.display_profile_data = !self.display_profile_data;
+            }
+
+            if !self.settings_open && !self.docs.is_empty() {
+                let pressed = |h: &raylib::prelude::RaylibHandle, k| {
+                    h.is_key_pressed(k) || h.is_key_pressed_repeat(k)
+                };
+                let last = self.docs.len() - 1;
+                if pressed(&self.raylib_handle, raylib::consts::KeyboardKey::KEY_DOWN) {
+                    self.selected_doc = Some(self.selected_doc.map_or(0, |i| (i + 1).min(last)));
+                }
+                if pressed(&self.raylib_handle, raylib::consts::KeyboardKey::KEY_UP) {
+                    self.selected_doc = Some(self.selected_doc.map_or(0, |i| i.saturating_sub(1)));
+                }
+                // keeps the selected result on screen
+                if let Some(i) = self.selected_doc {
+                    self.scrolling_to_top = false;
+                    let row = row_rect(list_rect, i);
+                    let top = search_rect.y + search_rect.height;
+                    if row.y + self.doc_offset < top {
+                        self.doc_offset = top - row.y;
+                    } else if row.y + row.height + self.doc_offset > w_h as f32 {
+                        self.doc_offset = w_h as f32 - row.y - row.height;
+                    }
+                }
+            }
+
+            if let Some(i) = self.selected_doc
+                && let Some(doc) = self.docs.get(i)
+            {
+                if self
+                    .raylib_handle
+                    .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                    && self
+                        .raylib_handle
+                        .is_key_pressed(raylib::consts::KeyboardKey::KEY_P)
+                {
+                    self.pinned.toggle(doc);
+                    self.pinned.save(&self.pinned_file);
+                    // pinned documents come first while browsing
+                    if self.last_terms.is_empty() {
+                        self.run_query();
+                    }
+                } else if !self.query_box_selected
+                    && !self.settings_open
+                    && self
+                        .raylib_handle
+                        .is_key_pressed(raylib::consts::KeyboardKey::KEY_ENTER)
+                    && let Some(p) = search_model::resolve_document_path(doc)
+                {
+                    self.conf.open_document(&p);
+                    self.opened.insert(doc.clone());
+                }
+            }
+
+            if !self.query_box_selected && !self.settings_open {
+                let number_keys = [
+                    raylib::consts::KeyboardKey::KEY_ONE,
+                    raylib::consts::KeyboardKey::KEY_TWO,
+                    raylib::consts::KeyboardKey::KEY_THREE,
+                    raylib::consts::KeyboardKey::KEY_FOUR,
+                    raylib::consts::KeyboardKey::KEY_FIVE,
+                    raylib::consts::KeyboardKey::KEY_SIX,
+                    raylib::consts::KeyboardKey::KEY_SEVEN,
+                    raylib::consts::KeyboardKey::KEY_EIGHT,
+                    raylib::consts::KeyboardKey::KEY_NINE,
+                ];
+                let pressed = number_keys
+                    .iter()
+                    .position(|k| self.raylib_handle.is_key_pressed(*k));
+                if let Some(doc) = pressed.and_then(|i| self.docs.get(i))
+                    && let Some(p) = search_model::resolve_document_path(doc)
+                {
+                    self.conf.open_document(&p);
+                    self.opened.insert(doc.clone());
+                }
+            }
+
+            if self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                && self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_L)
+            {
+                self.layout = self.layout.toggled();
+            }
+
+            if self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                && self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_K)
+            {
+                self.compact = !self.compact;
+            }
+
+            if self
+                .raylib_handle
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                && self
+                    .raylib_handle
+                    .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_SHIFT)
+                && self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_C)
+            {
+                self.copy_result_paths();
+            }
+
+            // previews the selected result, or the first one if nothing is selected
+            let previewed = self.docs.get(self.selected_doc.unwrap_or(0));
+            match previewed {
+                Some(doc) if preview_rect.is_some() => {
+                    if self.preview.as_ref().is_none_or(|p| &p.doc != doc) {
+                        self.preview = Some(Preview::new(doc, &mut self.text_cache));
+                    }
+                }
+                _ => self.preview = None,
+            }
+
             if self
                 .raylib_handle
-                .is_key_down(raylib::consts::KeyboardKey::KEY_SLASH)
+                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+                && self
+                    .raylib_handle
+                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_E)
             {
-                self.query_box_selected = true;
+                if self.conf.store_raw_terms {
+                    self.query_mode = match self.query_mode {
+                        search_model::QueryMode::Stemmed if self.conf.case_sensitive => {
+                            search_model::QueryMode::CaseSensitive
+                        }
+                        search_model::QueryMode::Stemmed => search_model::QueryMode::Exact,
+                        search_model::QueryMode::Exact | search_model::QueryMode::CaseSensitive => {
+                            search_model::QueryMode::Stemmed
+                        }
+                    };
+                    if !self.last_terms.is_empty() {
+                        self.run_query();
+                    }
+                } else {
+                    log::warn!("Exact search needs `store_raw_terms = true` (and a reindex)");
+                }
             }
-            if self.query_box_selected {
-                search_color = self.hover_color;
+
+            if settings_button.check_collision_point_rec(self.raylib_handle.get_mouse_position())
+                && self
+                    .raylib_handle
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT)
+            {
+                self.settings_open = !self.settings_open;
             }
 
-            self.scroll_velocity.y += self.raylib_handle.get_mouse_wheel_move_v().y * 10000.0;
-            self.scroll_velocity.y /= 1.2;
-            self.doc_offset += self.scroll_velocity.y * self.raylib_handle.get_frame_time();
-            self.doc_offset = self.doc_offset.clamp(-f32::MAX, 0.0);
+            // pressing the hint reindexes just like <r>, a reindex that's already running shows
+            // its progress in the search bar instead
+            let stale_rect = (self.index_stale && self.refresh.is_none()).then(|| {
+                let size = self.font.measure_text(STALE_HINT, 24.0, 0.0);
+                // the same distance from the top right corner as the settings button from the left,
+                // the search bar is up there without the title so it goes in the bottom one then
+                let y = if self.conf.show_title() {
+                    settings_button.y
+                } else {
+                    w_h as f32 - size.y - 16.0 - settings_button.y
+                };
+                raylib::math::Rectangle::new(
+                    w_w as f32 - size.x - 32.0 - settings_button.x,
+                    y,
+                    size.x + 32.0,
+                    size.y + 16.0,
+                )
+            });
+            if let Some(rect) = stale_rect
+                && !self.indexing()
+                && rect.check_collision_point_rec(self.raylib_handle.get_mouse_position())
+                && self
+                    .raylib_handle
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT)
+            {
+                self.refresh_model(false);
+            }
 
-            if self.query_box_selected {
-                if (self
+            if self.settings_open {
+                let mouse = self.raylib_handle.get_mouse_position();
+                let clicked = self
                     .raylib_handle
-                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_BACKSPACE)
-                    || self
-                        .raylib_handle
-                        .is_key_pressed_repeat(raylib::consts::KeyboardKey::KEY_BACKSPACE))
-                    && !self.query.is_empty()
-                {
-                    self.query.pop();
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT);
+                let mut changed = false;
+                let mut removed = None;
+                let mut reindexed = None;
+                // the directories can still be picked for searching, just not changed
+                let editable = clicked && !self.conf.read_only;
+                for (i, searched) in self.searched_directories.iter_mut().enumerate() {
+                    let row = row_rect(search_rect, i + 1);
+                    if editable && row_button(row).check_collision_point_rec(mouse) {
+                        removed = Some(i);
+                    } else if editable && second_row_button(row).check_collision_point_rec(mouse) {
+                        reindexed = Some(i);
+                    } else if clicked && row.check_collision_point_rec(mouse) {
+                        *searched = !*searched;
+                        changed = true;
+                    }
+                }
+                if let Some(i) = removed {
+                    self.remove_document_directory(i);
+                    changed = true;
+                }
+                if let Some(i) = reindexed {
+                    self.reindex_document_directory(i);
+                    changed = true;
                 }
 
-                while let Some(k) = self.raylib_handle.get_key_pressed() {
-                    let k = char::from_u32(k as u32);
-                    if let Some(k) = k {
-                        if k.is_ascii_alphanumeric() || k == ' ' {
-                            if !k.is_ascii_control() {
-                                self.query.push(k.to_ascii_lowercase());
-                            }
+                let add_row = row_rect(search_rect, self.conf.document_directories.len() + 1);
+                if clicked {
+                    self.new_directory_selected =
+                        !self.conf.read_only && add_row.check_collision_point_rec(mouse);
+                    if self.new_directory_selected {
+                        self.query_box_selected = false;
+                    }
+                }
+                let mut add = editable
+                    && row_button(add_row).check_collision_point_rec(mouse)
+                    && !self.new_directory.is_empty();
+                if self.new_directory_selected {
+                    while let Some(c) = self.raylib_handle.get_char_pressed() {
+                        if !c.is_control() {
+                            self.new_directory.push(c);
                         }
                     }
+                    if self
+                        .raylib_handle
+                        .is_key_pressed(raylib::consts::KeyboardKey::KEY_BACKSPACE)
+                        || self
+                            .raylib_handle
+                            .is_key_pressed_repeat(raylib::consts::KeyboardKey::KEY_BACKSPACE)
+                    {
+                        self.new_directory.pop();
+                    }
+                    if self
+                        .raylib_handle
+                        .is_key_pressed(raylib::consts::KeyboardKey::KEY_ESCAPE)
+                    {
+                        self.new_directory_selected = false;
+                    }
+                    add |= self
+                        .raylib_handle
+                        .is_key_pressed(raylib::consts::KeyboardKey::KEY_ENTER)
+                        && !self.new_directory.is_empty();
                 }
-
-                if self
-                    .raylib_handle
-                    .is_key_down(raylib::consts::KeyboardKey::KEY_ENTER)
-                {
-                    let terms: Vec<_> = self.query.split_whitespace().collect();
-                    let t = std::time::Instant::now();
-                    self.docs = search_model::do_query(&self.model, &terms);
-                    self.last_query_time = t.elapsed();
-                    self.doc_offset = 0.0;
+                if add {
+                    let p = std::mem::take(&mut self.new_directory);
+                    self.add_document_directory(p.trim());
+                    changed = true;
                 }
+                if changed {
+                    self.run_query();
+                }
+            } else {
+                self.new_directory_selected = false;
             }
 
-            if self
-                .raylib_handle
-                .is_key_pressed(raylib::consts::KeyboardKey::KEY_R)
-                && !self.query_box_selected
+            if !self.settings_open
+                && self.docs.is_empty()
+                && row_rect(list_rect, 0)
+                    .check_collision_point_rec(self.raylib_handle.get_mouse_position())
+                && self
+                    .raylib_handle
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT)
+                && let Some(suggestion) = self.suggestion.take()
             {
-                let t = std::time::Instant::now();
-                self.refresh_model();
-                self.reindex_time = t.elapsed();
-                self.docs.clear();
-                self.doc_offset = 0.0;
+                self.query = suggestion;
+                self.run_query();
             }
 
-            if self
-                .raylib_handle
-                .is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL)
+            // the second row of the empty index hint opens the settings
+            if !self.settings_open
+                && self.model.is_empty()
+                && !self.loading()
+                && row_rect(list_rect, 1)
+                    .check_collision_point_rec(self.raylib_handle.get_mouse_position())
                 && self
                     .raylib_handle
-                    .is_key_pressed(raylib::consts::KeyboardKey::KEY_D)
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT)
             {
-                self.display_profile_data = !self.display_profile_data;
+                self.settings_open = true;
+            }
+
+            // the next page gets loaded once the last row scrolls into view
+            if !self.browse_rest.is_empty()
+                && row_rect(list_rect, self.docs.len()).y + self.doc_offset < w_h as f32
+            {
+                self.show_more_docs();
+            }
+
+            if snippet_rows {
+                self.load_visible_snippets(list_rect, w_h as f32);
             }
 
+            let mouse = self.raylib_handle.get_mouse_position();
+            let released = self
+                .raylib_handle
+                .is_mouse_button_released(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT);
+            let mut hovered = None;
             for (i, d) in self.docs.iter().enumerate() {
-                let mut rect = search_rect;
+                let mut rect = row_rect(list_rect, i);
                 rect.y += self.doc_offset;
-                rect.y += (i + 1) as f32 * rect.height * 1.1;
 
-                if rect.y < w_h as f32 && rect.y > 0.0 {
-                    if rect.check_collision_point_rec(self.raylib_handle.get_mouse_position()) {
-                        if self.raylib_handle.is_mouse_button_down(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT) { 
-                            open::that(d).unwrap();
-                        }
-                    }
+                if self.settings_open
+                    || rect.y >= w_h as f32
+                    || rect.y <= 0.0
+                    || !rect.check_collision_point_rec(self.raylib_handle.get_mouse_position())
+                {
+                    continue;
+                }
+                hovered = Some(i);
+                if self
+                    .raylib_handle
+                    .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT)
+                {
+                    self.result_press = Some((d.clone(), mouse, self.doc_offset));
+                }
+                // NOTE: Opened on release so dragging across the list or flicking it with the
+                // wheel while the button is down doesn't launch whatever ends up under the cursor
+                if released
+                    && let Some((doc, at, offset)) = &self.result_press
+                    && doc == d
+                    && at.distance_to(mouse) <= CLICK_SLOP
+                    && (offset - self.doc_offset).abs() <= CLICK_SLOP
+                    && let Some(p) = search_model::resolve_document_path(d)
+                {
+                    self.conf.open_document(&p);
+                    self.opened.insert(d.clone());
+                }
+            }
+            if released {
+                self.result_press = None;
+            }
+            match hovered {
+                Some(i) if self.tooltip.as_ref().is_some_and(|t| t.doc == i) => {}
+                Some(i) => {
+                    let source = self.doc_sources.get(&self.docs[i]).map(String::as_str);
+                    self.tooltip = Some(Tooltip::new(i, &self.docs[i], &self.model, source));
                 }
+                None => self.tooltip = None,
             }
 
             self.update_time = update_time.elapsed();
 
+            let empty_index_hint = self.empty_index_hint();
             let mut d = self.raylib_handle.begin_drawing(&self.raylib_thread);
 
             let draw_time = std::time::Instant::now();
             d.clear_background(self.bg_color);
 
+            // the row tints are relative to the best result of the query
+            let top_score = self.scores.values().copied().fold(0.0, f64::max);
             for (i, doc) in self.docs.iter().enumerate() {
-                let mut rect = search_rect;
+                let mut rect = row_rect(list_rect, i);
                 rect.y += self.doc_offset;
-                rect.y += (i + 1) as f32 * rect.height * 1.1;
                 let mut result_color = self.idle_color;
+                if self.conf.result_highlight.row()
+                    && let Some(score) = self.scores.get(doc)
+                    && top_score > 0.0
+                {
+                    result_color = blend_color(
+                        self.idle_color,
+                        self.highlight_color,
+                        (score / top_score) as f32 * ROW_TINT_STRENGTH,
+                    );
+                }
+                if self.selected_doc == Some(i) {
+                    result_color = self.selection_color;
+                }
                 if rect.check_collision_point_rec(d.get_mouse_position()) {
                     result_color = self.hover_color;
 
@@ -567,20 +3100,199 @@ impl App {
                 }
                 if rect.y < w_h as f32 && rect.y > 0.0 {
                     d.draw_rectangle_rounded(rect, 0.1, 10, result_color);
-                    d.draw_scissor_mode(rect.x as i32, rect.y as i32, rect.width as i32, rect.height as i32, |mut d| {
-                        d.draw_text_ex(
-                            &self.font,
-                            doc,
-                            raylib::math::Vector2::new(
+                    d.draw_scissor_mode(
+                        rect.x as i32,
+                        rect.y as i32,
+                        rect.width as i32,
+                        rect.height as i32,
+                        |mut d| {
+                            // the path goes in the upper part of the row with a snippet under it
+                            let icon_pos = raylib::math::Vector2::new(
                                 rect.x + rect.width / 128.0,
-                                rect.y + rect.height / 4.0,
-                            ),
-                            32.0,
-                            0.0,
-                            self.fg_color,
-                        );
-                    });
+                                rect.y + rect.height / if snippet_rows { 8.0 } else { 4.0 },
+                            );
+                            d.draw_text_ex(
+                                &self.icon_font,
+                                file_type_icon(self.model.get(doc).and_then(|d| d.file_type())),
+                                icon_pos,
+                                font_size,
+                                0.0,
+                                self.fg_color,
+                            );
+                            let text_pos = raylib::math::Vector2::new(
+                                icon_pos.x + font_size * 1.25,
+                                icon_pos.y,
+                            );
+                            let terms: Vec<_> = if self.conf.result_highlight.terms() {
+                                self.last_terms.iter().map(|t| t.as_str()).collect()
+                            } else {
+                                vec![]
+                            };
+                            for r in search_model::highlight_ranges(doc, &terms, self.query_mode) {
+                                let before =
+                                    self.font.measure_text(&doc[..r.start], font_size, 0.0);
+                                let word = self.font.measure_text(&doc[r], font_size, 0.0);
+                                d.draw_rectangle_rec(
+                                    raylib::math::Rectangle::new(
+                                        text_pos.x + before.x,
+                                        text_pos.y,
+                                        word.x,
+                                        word.y,
+                                    ),
+                                    self.highlight_color,
+                                );
+                            }
+                            d.draw_text_ex(
+                                &self.font,
+                                doc,
+                                text_pos,
+                                font_size,
+                                0.0,
+                                self.fg_color,
+                            );
+                            if let Some(Some(snippet)) = self.snippets.get(doc) {
+                                let size = font_size * SNIPPET_FONT_SCALE;
+                                let pos = raylib::math::Vector2::new(
+                                    text_pos.x,
+                                    text_pos.y + font_size * 1.1,
+                                );
+                                for r in
+                                    search_model::highlight_ranges(snippet, &terms, self.query_mode)
+                                {
+                                    let before =
+                                        self.font.measure_text(&snippet[..r.start], size, 0.0);
+                                    let word = self.font.measure_text(&snippet[r], size, 0.0);
+                                    d.draw_rectangle_rec(
+                                        raylib::math::Rectangle::new(
+                                            pos.x + before.x,
+                                            pos.y,
+                                            word.x,
+                                            word.y,
+                                        ),
+                                        self.highlight_color,
+                                    );
+                                }
+                                d.draw_text_ex(&self.font, snippet, pos, size, 0.0, self.fg_color);
+                            }
+                            // the score and, for results from merged indexes, the name of their
+                            // index go at the end
+                            let score = self
+                                .conf
+                                .show_scores
+                                .zip(self.scores.get(doc))
+                                .map(|(scale, s)| score_label(scale, *s));
+                            let label: Vec<&str> = score
+                                .as_deref()
+                                .into_iter()
+                                .chain(self.doc_sources.get(doc).map(String::as_str))
+                                .collect();
+                            if !label.is_empty() {
+                                let label = label.join("  ");
+                                let size = self.font.measure_text(&label, font_size, 0.0);
+                                let pos = raylib::math::Vector2::new(
+                                    rect.x + rect.width - size.x - font_size * 2.0,
+                                    text_pos.y,
+                                );
+                                d.draw_rectangle_rec(
+                                    raylib::math::Rectangle::new(pos.x, pos.y, size.x, size.y),
+                                    result_color,
+                                );
+                                d.draw_text_ex(
+                                    &self.font,
+                                    &label,
+                                    pos,
+                                    font_size,
+                                    0.0,
+                                    self.fg_color,
+                                );
+                            }
+                            if self.pinned.contains(doc) {
+                                d.draw_text_ex(
+                                    &self.icon_font,
+                                    ICON_PIN,
+                                    raylib::math::Vector2::new(
+                                        rect.x + rect.width - font_size * 1.5,
+                                        text_pos.y,
+                                    ),
+                                    font_size,
+                                    0.0,
+                                    self.fg_color,
+                                );
+                            }
+                        },
+                    );
+                }
+            }
+
+            if self.model_loader.is_some() {
+                let rect = row_rect(list_rect, self.docs.len());
+                d.draw_text_ex(
+                    &self.font,
+                    "Loading index...",
+                    raylib::math::Vector2::new(
+                        rect.x + rect.width / 128.0,
+                        rect.y + rect.height / 4.0,
+                    ),
+                    font_size,
+                    0.0,
+                    self.fg_color,
+                );
+            }
+
+            if !self.settings_open
+                && let Some(hint) = &empty_index_hint
+            {
+                let rect = row_rect(list_rect, 0);
+                d.draw_text_ex(
+                    &self.font,
+                    hint,
+                    raylib::math::Vector2::new(
+                        rect.x + rect.width / 128.0,
+                        rect.y + rect.height / 4.0,
+                    ),
+                    font_size,
+                    0.0,
+                    self.fg_color,
+                );
+                let rect = row_rect(list_rect, 1);
+                let mut settings_color = self.idle_color;
+                if rect.check_collision_point_rec(d.get_mouse_position()) {
+                    settings_color = self.hover_color;
+                }
+                d.draw_rectangle_rounded(rect, 0.1, 10, settings_color);
+                d.draw_text_ex(
+                    &self.font,
+                    "Add files or directories in the settings",
+                    raylib::math::Vector2::new(
+                        rect.x + rect.width / 128.0,
+                        rect.y + rect.height / 4.0,
+                    ),
+                    font_size,
+                    0.0,
+                    self.fg_color,
+                );
+            }
+
+            if self.docs.is_empty()
+                && let Some(suggestion) = &self.suggestion
+            {
+                let rect = row_rect(list_rect, 0);
+                let mut suggestion_color = self.idle_color;
+                if rect.check_collision_point_rec(d.get_mouse_position()) {
+                    suggestion_color = self.hover_color;
                 }
+                d.draw_rectangle_rounded(rect, 0.1, 10, suggestion_color);
+                d.draw_text_ex(
+                    &self.font,
+                    &format!("Did you mean: {suggestion}"),
+                    raylib::math::Vector2::new(
+                        rect.x + rect.width / 128.0,
+                        rect.y + rect.height / 4.0,
+                    ),
+                    font_size,
+                    0.0,
+                    self.fg_color,
+                );
             }
 
             // draws a mask for the search results so when the user scrolls down the search results
@@ -593,24 +3305,345 @@ impl App {
                 self.bg_color,
             );
 
-            // ehhh i dont know how i feel about the label i dont want to be so pretentious
-            d.draw_text_ex(&self.font, label_text, label_pos, 64.0, 0.0, self.fg_color);
+            if let Some(rect) = preview_rect {
+                d.draw_rectangle_rounded(rect, 0.02, 10, self.idle_color);
+                let text = match &self.preview {
+                    Some(Preview { text: Some(t), .. }) => t.as_str(),
+                    Some(Preview { text: None, .. }) => "No preview available",
+                    None => "",
+                };
+                let char_size = self.font.measure_text("M", 24.0, 0.0);
+                let padding = 16.0;
+                let lines = wrap_text(
+                    text,
+                    ((rect.width - padding * 2.0) / char_size.x) as usize,
+                    ((rect.height - padding * 2.0) / char_size.y) as usize,
+                );
+                d.draw_scissor_mode(
+                    rect.x as i32,
+                    rect.y as i32,
+                    rect.width as i32,
+                    rect.height as i32,
+                    |mut d| {
+                        for (i, line) in lines.iter().enumerate() {
+                            d.draw_text_ex(
+                                &self.font,
+                                line,
+                                raylib::math::Vector2::new(
+                                    rect.x + padding,
+                                    rect.y + padding + i as f32 * char_size.y,
+                                ),
+                                24.0,
+                                0.0,
+                                self.fg_color,
+                            );
+                        }
+                    },
+                );
+            }
 
-            d.draw_rectangle_rounded(search_rect, 0.1, 10, search_color);
+            if self.settings_open {
+                d.draw_rectangle(
+                    0,
+                    (search_rect.y + search_rect.height) as i32,
+                    w_w,
+                    w_h,
+                    self.bg_color,
+                );
+                let header = row_rect(search_rect, 0);
+                d.draw_text_ex(
+                    &self.font,
+                    "Search in:",
+                    raylib::math::Vector2::new(
+                        header.x + header.width / 128.0,
+                        header.y + header.height / 4.0,
+                    ),
+                    32.0,
+                    0.0,
+                    self.fg_color,
+                );
+                for (i, (dir, searched)) in self
+                    .conf
+                    .document_directories
+                    .iter()
+                    .zip(&self.searched_directories)
+                    .enumerate()
+                {
+                    let rect = row_rect(search_rect, i + 1);
+                    let mut row_color = self.idle_color;
+                    if rect.check_collision_point_rec(d.get_mouse_position()) {
+                        row_color = self.hover_color;
+                    }
+                    d.draw_rectangle_rounded(rect, 0.1, 10, row_color);
+                    let check = raylib::math::Rectangle::new(
+                        rect.x + rect.width / 128.0,
+                        rect.y + rect.height * 0.2,
+                        rect.height * 0.6,
+                        rect.height * 0.6,
+                    );
+                    if *searched {
+                        d.draw_rectangle_rec(check, self.fg_color);
+                    } else {
+                        d.draw_rectangle_lines_ex(check, 2.0, self.fg_color);
+                    }
+                    d.draw_text_ex(
+                        &self.font,
+                        dir,
+                        raylib::math::Vector2::new(
+                            check.x + check.width * 1.5,
+                            rect.y + rect.height / 4.0,
+                        ),
+                        32.0,
+                        0.0,
+                        self.fg_color,
+                    );
+                    let buttons = if self.conf.read_only {
+                        vec![]
+                    } else {
+                        vec![
+                            (second_row_button(rect), ICON_REINDEX),
+                            (row_button(rect), ICON_REMOVE),
+                        ]
+                    };
+                    for (button, icon) in buttons {
+                        d.draw_text_ex(
+                            &self.icon_font,
+                            icon,
+                            raylib::math::Vector2::new(
+                                button.x + button.width / 4.0,
+                                button.y + button.height / 4.0,
+                            ),
+                            32.0,
+                            0.0,
+                            self.fg_color,
+                        );
+                    }
+                }
 
-            d.draw_scissor_mode(search_rect.x as i32, search_rect.y as i32, search_rect.width as i32, search_rect.height as i32, |mut d| {
+                let add_row = row_rect(search_rect, self.conf.document_directories.len() + 1);
+                let mut add_color = self.idle_color;
+                if self.new_directory_selected {
+                    add_color = self.hover_color;
+                }
+                d.draw_rectangle_rounded(add_row, 0.1, 10, add_color);
+                let text = if self.conf.read_only {
+                    "The index is read only"
+                } else if self.new_directory.is_empty() && !self.new_directory_selected {
+                    "Add a directory..."
+                } else {
+                    self.new_directory.as_str()
+                };
                 d.draw_text_ex(
                     &self.font,
-                    &self.query,
+                    text,
                     raylib::math::Vector2::new(
-                        search_rect.x + search_rect.x / 16.0 + (search_rect.x + search_rect.x / 128.0),
-                        search_rect.y + search_rect.y / 16.0,
+                        add_row.x + add_row.width / 128.0,
+                        add_row.y + add_row.height / 4.0,
                     ),
                     32.0,
                     0.0,
                     self.fg_color,
                 );
-            });
+                if !self.conf.read_only {
+                    let add = row_button(add_row);
+                    d.draw_text_ex(
+                        &self.icon_font,
+                        ICON_ADD,
+                        raylib::math::Vector2::new(
+                            add.x + add.width / 4.0,
+                            add.y + add.height / 4.0,
+                        ),
+                        32.0,
+                        0.0,
+                        self.fg_color,
+                    );
+                }
+            }
+
+            let mut settings_color = self.idle_color;
+            if settings_button.check_collision_point_rec(d.get_mouse_position())
+                || self.settings_open
+            {
+                settings_color = self.hover_color;
+            }
+            d.draw_rectangle_rounded(settings_button, 0.1, 10, settings_color);
+            // the icon takes up the middle two thirds of the button
+            d.draw_text_ex(
+                &self.icon_font,
+                ICON_SETTINGS,
+                raylib::math::Vector2::new(
+                    settings_button.x + settings_button.width / 6.0,
+                    settings_button.y + settings_button.height / 6.0,
+                ),
+                settings_button.width * 2.0 / 3.0,
+                0.0,
+                self.fg_color,
+            );
+
+            if let Some(rect) = stale_rect {
+                let color = if rect.check_collision_point_rec(d.get_mouse_position()) {
+                    self.hover_color
+                } else {
+                    self.idle_color
+                };
+                d.draw_rectangle_rounded(rect, 0.1, 10, color);
+                d.draw_text_ex(
+                    &self.font,
+                    STALE_HINT,
+                    raylib::math::Vector2::new(rect.x + 16.0, rect.y + 8.0),
+                    24.0,
+                    0.0,
+                    self.fg_color,
+                );
+            }
+
+            // ehhh i dont know how i feel about the label i dont want to be so pretentious
+            if self.conf.show_title() {
+                d.draw_text_ex(&self.font, label_text, label_pos, 64.0, 0.0, self.fg_color);
+            }
+
+            d.draw_rectangle_rounded(search_rect, 0.1, 10, search_color);
+            let indexing = self.refresh.as_ref().map(Refresh::status);
+            if let Some(refresh) = &self.refresh {
+                // along the bottom edge, clear of the rounded corners
+                let inset = search_rect.height / 8.0;
+                d.draw_rectangle_rec(
+                    raylib::math::Rectangle::new(
+                        search_rect.x + inset,
+                        search_rect.y + search_rect.height - 4.0,
+                        (search_rect.width - inset * 2.0) * refresh.fraction(),
+                        4.0,
+                    ),
+                    self.highlight_color,
+                );
+            }
+
+            d.draw_scissor_mode(
+                search_rect.x as i32,
+                search_rect.y as i32,
+                search_rect.width as i32,
+                search_rect.height as i32,
+                |mut d| {
+                    d.draw_text_ex(
+                        &self.font,
+                        &self.query,
+                        raylib::math::Vector2::new(
+                            search_rect.x
+                                + search_rect.x / 16.0
+                                + (search_rect.x + search_rect.x / 128.0),
+                            search_rect.y + search_rect.y / 16.0,
+                        ),
+                        32.0,
+                        0.0,
+                        self.fg_color,
+                    );
+                    let mut status = vec![];
+                    if let Some(indexing) = &indexing {
+                        status.push(indexing.as_str());
+                    }
+                    // how many documents the last query found, so refining it gives feedback
+                    let count = match self.docs.len() + self.browse_rest.len() {
+                        1 => "1 result".to_string(),
+                        n => format!("{n} results"),
+                    };
+                    if !self.last_terms.is_empty() {
+                        status.push(count.as_str());
+                    }
+                    if self.partial_results {
+                        status.push("partial results");
+                    }
+                    match self.query_mode {
+                        search_model::QueryMode::Stemmed => {}
+                        search_model::QueryMode::Exact => status.push("exact"),
+                        search_model::QueryMode::CaseSensitive => status.push("case sensitive"),
+                    }
+                    if !status.is_empty() {
+                        let status = status.join(" | ");
+                        let size = self.font.measure_text(&status, 32.0, 0.0);
+                        d.draw_text_ex(
+                            &self.font,
+                            &status,
+                            raylib::math::Vector2::new(
+                                search_rect.x + search_rect.width - size.x - 16.0,
+                                search_rect.y + search_rect.y / 16.0,
+                            ),
+                            32.0,
+                            0.0,
+                            self.fg_color,
+                        );
+                    }
+                },
+            );
+            if let Some(tooltip) = &self.tooltip
+                && tooltip.since.elapsed() > TOOLTIP_DELAY
+            {
+                let size = self.font.measure_text(&tooltip.text, 24.0, 0.0);
+                let mouse = d.get_mouse_position();
+                // kept inside of the window
+                let rect = raylib::math::Rectangle::new(
+                    (mouse.x + 16.0).min(w_w as f32 - size.x - 32.0).max(0.0),
+                    (mouse.y + 16.0).min(w_h as f32 - size.y - 32.0).max(0.0),
+                    size.x + 16.0,
+                    size.y + 16.0,
+                );
+                d.draw_rectangle_rounded(rect, 0.1, 10, self.idle_color);
+                d.draw_rectangle_rounded_lines_ex(rect, 0.1, 10, 2.0, self.fg_color);
+                d.draw_text_ex(
+                    &self.font,
+                    &tooltip.text,
+                    raylib::math::Vector2::new(rect.x + 8.0, rect.y + 8.0),
+                    24.0,
+                    0.0,
+                    self.fg_color,
+                );
+            }
+            if self.help_open {
+                let binds: Vec<_> = KEYBINDS
+                    .iter()
+                    .filter(|(k, _)| !(self.conf.read_only && REINDEX_KEYBINDS.contains(k)))
+                    .filter(|(k, _)| self.conf.store_raw_terms || *k != EXACT_KEYBIND)
+                    .collect();
+                let font_size = 24.0;
+                let line_height = font_size * 1.4;
+                let key_width = binds
+                    .iter()
+                    .map(|(k, _)| self.font.measure_text(k, font_size, 0.0).x)
+                    .fold(0.0, f32::max);
+                let action_width = binds
+                    .iter()
+                    .map(|(_, a)| self.font.measure_text(a, font_size, 0.0).x)
+                    .fold(0.0, f32::max);
+                let width = key_width + action_width + 64.0;
+                let height = line_height * binds.len() as f32 + 32.0;
+                d.draw_rectangle(0, 0, w_w, w_h, raylib::color::Color::new(0, 0, 0, 127));
+                let rect = raylib::math::Rectangle::new(
+                    ((w_w as f32 - width) / 2.0).max(0.0),
+                    ((w_h as f32 - height) / 2.0).max(0.0),
+                    width,
+                    height,
+                );
+                d.draw_rectangle_rounded(rect, 0.05, 10, self.idle_color);
+                d.draw_rectangle_rounded_lines_ex(rect, 0.05, 10, 2.0, self.fg_color);
+                for (i, (key, action)) in binds.iter().enumerate() {
+                    let y = rect.y + 16.0 + line_height * i as f32;
+                    d.draw_text_ex(
+                        &self.font,
+                        key,
+                        raylib::math::Vector2::new(rect.x + 16.0, y),
+                        font_size,
+                        0.0,
+                        self.fg_color,
+                    );
+                    d.draw_text_ex(
+                        &self.font,
+                        action,
+                        raylib::math::Vector2::new(rect.x + key_width + 48.0, y),
+                        font_size,
+                        0.0,
+                        self.fg_color,
+                    );
+                }
+            }
             self.draw_time = draw_time.elapsed();
 
             if self.display_profile_data {
@@ -638,15 +3671,187 @@ impl App {
                 );
             }
         }
+        self.shutdown();
+    }
+
+    // runs once the window is closed, anything not saved yet has to be written here
+    fn shutdown(mut self) {
+        // a rebuild still running in the background would get cut off halfway through writing
+        // the index
+        if let Some(loader) = self.model_loader.take() {
+            log::info!("Waiting for the index to finish loading");
+            if loader.join().is_ok_and(|l| l.rebuilt) {
+                self.index_state
+                    .mark_indexed(&self.conf.document_directories);
+                self.index_state.save(&self.index_state_file);
+            }
+        }
+        // NOTE: A reindex that isn't done yet only found part of the files, swapping it in would
+        // lose the rest. It gets called off and waited for (just the files it's reading right now)
+        // instead of being killed halfway through, the index stays out of date until the next one
+        self.cancel_auto_reindex();
+        if let Some(refresh) = self.refresh.take() {
+            log::info!("Calling off the reindex");
+            refresh
+                .cancel
+                .store(true, std::sync::atomic::Ordering::Relaxed);
+            let _ = refresh.worker.join();
+        }
+        for rebuild in self.cancelled_rebuilds.drain(..) {
+            let _ = rebuild.join();
+        }
+        if self.index_dirty {
+            self.save_index();
+            log::info!("Saved the index");
+        }
+        // a minimized window reports the size and position of its icon
+        if !self.raylib_handle.is_window_minimized() {
+            let position = self.raylib_handle.get_window_position();
+            WindowState {
+                size: Some((
+                    self.raylib_handle.get_screen_width(),
+                    self.raylib_handle.get_screen_height(),
+                )),
+                position: Some((position.x as i32, position.y as i32)),
+            }
+            .save(&self.window_state_file);
+        }
+        log::logger().flush();
         // NOTE: Because the drop order causes the raylib handle to be closed before any assets get
         // unloaded we HAVE to drop them manually before EOL
         drop(self.font);
+        drop(self.icon_font);
+    }
+}
+
+fn main() -> std::process::ExitCode {
+    logging::init();
+    match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(Some(opts)) => return cli::run(&opts),
+        Ok(None) => {}
+        Err(e) => {
+            log::error!("{e}");
+            eprintln!("{}", cli::USAGE);
+            return std::process::ExitCode::FAILURE;
+        }
     }
+    if let Some(app) = App::new() {
+        app.run()
+    }
+    std::process::ExitCode::SUCCESS
 }
 
-fn main() {
-    match App::new() {
-        Some(app) => app.run(),
-        None => {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contrast_ratio() {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(0xff, 0xff, 0xff);
+        assert!((black.contrast(white) - 21.0).abs() < 0.01);
+        assert_eq!(white.contrast(white), 1.0);
+        assert_eq!(black.contrast(white), white.contrast(black));
+    }
+
+    #[test]
+    fn readable_behind_keeps_readable_colors() {
+        let text = Color::new(0xcc, 0xcc, 0xcc);
+        let highlight = Color::new(0x5c, 0x4b, 0x1e);
+        assert_eq!(highlight.readable_behind(text, 3.0).hex(), highlight.hex());
+    }
+
+    #[test]
+    fn readable_behind_adjusts_low_contrast() {
+        // light text on a light highlight gets a darker one
+        let text = Color::new(0xee, 0xee, 0xee);
+        let highlight = Color::new(0xdd, 0xdd, 0x99);
+        let readable = highlight.readable_behind(text, 4.5);
+        assert!(readable.contrast(text) >= 4.5);
+        assert!(readable.luminance() < highlight.luminance());
+        // and dark text a lighter one
+        let text = Color::new(0x22, 0x22, 0x22);
+        let highlight = Color::new(0x33, 0x33, 0x55);
+        let readable = highlight.readable_behind(text, 4.5);
+        assert!(readable.contrast(text) >= 4.5);
+        assert!(readable.luminance() > highlight.luminance());
+    }
+
+    #[test]
+    fn typed_queries_keep_their_case() {
+        let dir = std::env::temp_dir().join(format!("local-search-case-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("upper.txt"), "the IT department").unwrap();
+        std::fs::write(dir.join("lower.txt"), "it works").unwrap();
+        std::fs::write(dir.join("other.txt"), "nothing here").unwrap();
+        let opts = search_model::IndexOptions {
+            store_raw_terms: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let model = search_model::analyze_dir(&dir, &opts).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let dict = search_model::TermDictionary::build(&model);
+        let search = |typed: &str, mode| {
+            let mut query = String::new();
+            for c in typed.chars() {
+                push_query_char(&mut query, c, 64);
+            }
+            let terms = split_query(&query, false);
+            let terms: Vec<&str> = terms.iter().map(String::as_str).collect();
+            let (docs, _) = search_model::do_query(
+                &model,
+                &dict,
+                &terms,
+                mode,
+                None,
+                search_model::Scoring::default(),
+                search_model::ScoreScale::default(),
+            );
+            docs.into_iter()
+                .map(|(p, _)| std::path::Path::new(&p).file_name().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            search("IT", search_model::QueryMode::CaseSensitive),
+            ["upper.txt"]
+        );
+        assert_eq!(
+            search("it", search_model::QueryMode::CaseSensitive),
+            ["lower.txt"]
+        );
+        // stemmed search still ignores it
+        assert_eq!(search("IT", search_model::QueryMode::Stemmed).len(), 2);
+    }
+
+    #[test]
+    fn typed_queries_keep_any_script() {
+        let mut query = String::new();
+        for c in "日本語  my_tag:rust!".chars() {
+            push_query_char(&mut query, c, 64);
+        }
+        assert_eq!(query, "日本語 my_tag:rust");
+        assert_eq!(split_query(&query, true), ["日本", "本語", "my_tag:rust"]);
+    }
+
+    #[test]
+    fn index_keys_relative_to_the_root() {
+        let root = std::path::Path::new("/home/me/docs");
+        let doc = "/home/me/docs/notes/bundle.zip!/a.txt";
+        assert_eq!(index_key(Some(root), doc), "notes/bundle.zip!/a.txt");
+        // loaded on another machine with its own root
+        let other = std::path::Path::new("/Users/me/docs");
+        assert_eq!(
+            resolve_index_key(Some(other), index_key(Some(root), doc)),
+            "/Users/me/docs/notes/bundle.zip!/a.txt"
+        );
+        // outside of the root or without one the paths stay as they are
+        assert_eq!(index_key(Some(root), "/tmp/a.txt"), "/tmp/a.txt");
+        assert_eq!(index_key(None, doc), doc);
+        assert_eq!(
+            resolve_index_key(Some(other), "/tmp/a.txt".into()),
+            "/tmp/a.txt"
+        );
     }
 }
@@ -1,161 +1,3302 @@
-use std::{collections::HashMap, io::BufReader, str::FromStr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufReader,
+};
 use wincode::{SchemaRead, SchemaWrite};
 
-fn create_document_from_text(text: &str) -> Document {
-    let stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
-    let mut words_map = HashMap::new();
-    let mut current_word = String::new();
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\'' || c == '-'
+}
+
+// hiragana, katakana, hangul and the cjk ideographs, none of which put spaces between words
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff66}'..='\u{ff9f}')
+}
 
-    let add_to_map = |word: &str, map: &mut HashMap<String, usize>| {
-        if !word.is_empty() {
-            let word = stemmer.stem(&word.to_lowercase()).to_string();
-            *map.entry(word).or_insert(0) += 1;
+// a lone character stays as is, longer runs become overlapping pairs ("日本語" -> "日本", "本語")
+fn cjk_bigrams(run: &[char]) -> Vec<String> {
+    if run.len() == 1 {
+        return vec![run[0].to_string()];
+    }
+    run.windows(2).map(|w| w.iter().collect()).collect()
+}
+
+/// Splits the cjk parts of a query term into the same bigrams `IndexOptions::cjk_bigrams` indexes,
+/// a `^weight` suffix is kept on every piece
+pub fn split_cjk_term(t: &str) -> Vec<String> {
+    let (term, weight) = parse_weighted_term(t);
+    let suffix = &t[term.len()..];
+    let mut pieces = vec![];
+    let mut current = String::new();
+    let mut run = vec![];
+    for c in term.chars() {
+        if is_cjk(c) {
+            if !current.is_empty() {
+                pieces.push(std::mem::take(&mut current));
+            }
+            run.push(c);
+        } else {
+            pieces.extend(cjk_bigrams(&run));
+            run.clear();
+            current.push(c);
         }
-    };
+    }
+    pieces.extend(cjk_bigrams(&run));
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    if weight == 1.0 {
+        return pieces;
+    }
+    pieces.into_iter().map(|p| p + suffix).collect()
+}
+
+fn create_document_from_text(text: &str, opts: &IndexOptions) -> Document {
+    create_document_from_field(tokenize(text, opts))
+}
+
+fn create_document_from_field(body: Field) -> Document {
+    let Field {
+        words,
+        raw_words,
+        positions,
+    } = body;
+    Document {
+        words,
+        raw_words,
+        positions,
+        fields: HashMap::new(),
+        modified: 0,
+        size: 0,
+        file_type: None,
+        content_hash: 0,
+    }
+}
+
+fn tokenize(text: &str, opts: &IndexOptions) -> Field {
+    let mut tokenizer = Tokenizer::new(opts);
+    tokenizer.push_str(text);
+    tokenizer.finish()
+}
+
+// turns text into term counts as it comes in, so big files never have to be in memory as a whole
+struct Tokenizer<'a> {
+    opts: &'a IndexOptions,
+    stemmer: rust_stemmers::Stemmer,
+    field: Field,
+    current_word: String,
+    // characters in `current_word`, including the ones past `IndexOptions::max_token_length` that
+    // didn't get kept
+    word_len: usize,
+    cjk_run: Vec<char>,
+    // how many terms came before the next one
+    position: u32,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(opts: &'a IndexOptions) -> Self {
+        Self {
+            opts,
+            stemmer: rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English),
+            field: Field::default(),
+            current_word: String::new(),
+            word_len: 0,
+            cjk_run: vec![],
+            position: 0,
+        }
+    }
+
+    fn add_term(&mut self, word: &str) {
+        if word.is_empty() {
+            return;
+        }
+        let lowercase = word.to_lowercase();
+        let stemmed = self.stemmer.stem(&lowercase).to_string();
+        if self.opts.store_positions {
+            self.field
+                .positions
+                .entry(stemmed.clone())
+                .or_default()
+                .push(self.position);
+        }
+        self.position += 1;
+        *self.field.words.entry(stemmed).or_insert(0) += 1;
+        if self.opts.store_raw_terms {
+            let raw = if self.opts.case_sensitive {
+                word.to_string()
+            } else {
+                lowercase
+            };
+            *self.field.raw_words.entry(raw).or_insert(0) += 1;
+        }
+    }
 
-    for c in text.chars() {
-        if c.is_alphanumeric() || c == '\'' || c == '-' {
-            current_word.push(c);
+    // words over the max length (base64 blobs, urls...) are dropped, they'd never be searched for
+    // and only skew the term frequencies
+    fn flush_word(&mut self) {
+        let word = std::mem::take(&mut self.current_word);
+        let too_long = self
+            .opts
+            .max_token_length
+            .is_some_and(|max| self.word_len > max);
+        self.word_len = 0;
+        if !too_long {
+            self.add_term(&word);
+        }
+    }
+
+    fn flush_cjk_run(&mut self) {
+        if self.cjk_run.is_empty() {
+            return;
+        }
+        for bigram in cjk_bigrams(&self.cjk_run) {
+            self.add_term(&bigram);
+        }
+        self.cjk_run.clear();
+    }
+
+    fn push(&mut self, c: char) {
+        if self.opts.cjk_bigrams && is_cjk(c) {
+            self.flush_word();
+            self.cjk_run.push(c);
+            return;
+        }
+        self.flush_cjk_run();
+        if is_word_char(c) {
+            self.word_len += 1;
+            // no need to keep a word around that gets dropped anyway
+            if self
+                .opts
+                .max_token_length
+                .is_none_or(|max| self.word_len <= max)
+            {
+                self.current_word.push(c);
+            }
         } else {
-            add_to_map(&current_word, &mut words_map);
-            current_word.clear();
+            self.flush_word();
             if !c.is_whitespace() {
-                add_to_map(&c.to_string(), &mut words_map);
+                self.add_term(&c.to_string());
             }
         }
     }
 
-    add_to_map(&current_word, &mut words_map);
+    fn push_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.push(c);
+        }
+    }
+
+    fn finish(mut self) -> Field {
+        self.flush_word();
+        self.flush_cjk_run();
+        self.field
+    }
+}
 
-    Document { words: words_map }
+/// Settings that change what ends up in the index
+#[derive(Debug, Clone, Default)]
+pub struct IndexOptions {
+    /// adds the year and year-month (e.g. "2023" and "2023-03") of the modification date as terms
+    pub index_dates: bool,
+    /// how many levels of subdirectories get descended into, unlimited if `None`
+    pub max_depth: Option<usize>,
+    /// files that get skipped
+    pub excluded_files: HashSet<std::path::PathBuf>,
+    /// also keeps the lowercased but unstemmed terms around for `QueryMode::Exact`
+    pub store_raw_terms: bool,
+    /// the raw terms keep their case instead, for `QueryMode::CaseSensitive`
+    pub case_sensitive: bool,
+    /// keeps where in the body every (stemmed) term is, for `NEAR/<n>` queries
+    pub store_positions: bool,
+    /// indexes the values of xml/xhtml attributes (like `<meta content="...">`) as body text
+    pub xml_attributes: bool,
+    /// indexes the notes of pdf annotations (comments, notes on highlights) as a boosted field
+    pub pdf_annotations: bool,
+    /// splits chinese/japanese/korean text into character pairs instead of one token per sentence
+    pub cjk_bigrams: bool,
+    /// lowercase tags of .txt lines (`title` for `Title: ...`) whose text goes into a field of the
+    /// same name instead of the body
+    pub field_tags: Vec<String>,
+    /// words longer than this many characters are left out, no limit if `None`
+    pub max_token_length: Option<usize>,
+    /// indexes the directory names and the file name of every document as a boosted field
+    pub path_terms: bool,
+    /// logs how many characters got extracted from every file, for spotting extraction failures
+    pub log_extracted_chars: bool,
 }
 
-#[derive(Debug)]
-enum FileType {
+/// Which of the term maps of a document a query gets matched against
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QueryMode {
+    /// "running" matches "run", "runs"...
+    #[default]
+    Stemmed,
+    /// "running" only matches "running", needs `IndexOptions::store_raw_terms`
+    Exact,
+    /// "IT" only matches "IT" and not "it", needs `IndexOptions::case_sensitive` too
+    CaseSensitive,
+}
+
+impl QueryMode {
+    fn normalize(self, stemmer: &rust_stemmers::Stemmer, term: &str) -> String {
+        let term = self.fold_case(term);
+        match self {
+            Self::Stemmed => stemmer.stem(&term).to_string(),
+            Self::Exact | Self::CaseSensitive => term,
+        }
+    }
+
+    // `Stemmed` becomes `Exact`, for the `=term` terms of a query
+    fn unstemmed(self) -> Self {
+        match self {
+            Self::Stemmed => Self::Exact,
+            m => m,
+        }
+    }
+
+    // lowercases the term unless the case has to match
+    fn fold_case(self, term: &str) -> String {
+        match self {
+            Self::CaseSensitive => term.to_string(),
+            Self::Stemmed | Self::Exact => term.to_lowercase(),
+        }
+    }
+}
+
+/// What the term counts get divided by for the term frequency
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TfNormalization {
+    /// every term of the document, repeated ones included
+    #[default]
+    Total,
+    /// the distinct terms of the document, so repetitive documents don't get pushed down as much
+    Unique,
+}
+
+/// How the matches of a query get scored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scoring {
+    /// the term frequency (see `TfNormalization`) times how rare the term is across the documents
+    TfIdf(TfNormalization),
+    /// Okapi BM25, more occurrences of a term count less and less (`k1` is how fast they stop
+    /// counting) and documents longer than the average get pushed down (`b` is how much, 0-1)
+    Bm25 { k1: f64, b: f64 },
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self::TfIdf(TfNormalization::default())
+    }
+}
+
+/// The usual BM25 parameters, see `Scoring::Bm25`
+pub const BM25_K1: f64 = 1.2;
+pub const BM25_B: f64 = 0.75;
+
+// the BM25 weight of a term that `freq` of the `documents` have, never negative unlike the one of
+// the original formula for terms in more than half of them
+fn bm25_idf(documents: usize, freq: usize) -> f64 {
+    ((documents as f64 - freq as f64 + 0.5) / (freq as f64 + 0.5) + 1.0).ln()
+}
+
+/// What the scores `do_query` hands back are scaled to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreScale {
+    /// the plain tf-idf, only comparable between results of the same query
+    #[default]
+    Raw,
+    /// 0-100, the percentage of the best score any document could get for the query
+    Percent,
+}
+
+// converts unix seconds into a (year, month, day) civil date
+// see: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_date(secs: u64) -> (i64, u32, u32) {
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats unix seconds as YYYY-MM-DD (UTC)
+pub fn format_date(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+// the inverse of `civil_date`, gives the days since the unix epoch
+// see: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// A `after:YYYY-MM-DD` or `before:YYYY-MM-DD` query term, both days are included
+#[derive(Debug, Clone, Copy)]
+enum DateFilter {
+    // unix seconds of the first moment of the day
+    After(u64),
+    // unix seconds of the first moment of the next day
+    Before(u64),
+}
+
+impl DateFilter {
+    fn parse(t: &str) -> Option<Self> {
+        let (op, date) = t.split_once(':')?;
+        let mut parts = date.split('-');
+        let year: i64 = parts.next()?.parse().ok()?;
+        let month: u32 = parts.next()?.parse().ok()?;
+        let day: u32 = parts.next()?.parse().ok()?;
+        if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day).max(0) as u64;
+        match op {
+            "after" => Some(Self::After(days * 86400)),
+            "before" => Some(Self::Before((days + 1) * 86400)),
+            _ => None,
+        }
+    }
+
+    // documents with an unknown modification time never match
+    fn matches(self, modified: u64) -> bool {
+        match self {
+            Self::After(t) => modified != 0 && modified >= t,
+            Self::Before(t) => modified != 0 && modified < t,
+        }
+    }
+}
+
+/// A `size:>10mb` or `size:<1kb` query term, the units are kb, mb and gb (powers of 1024) and
+/// plain bytes without one
+#[derive(Debug, Clone, Copy)]
+enum SizeFilter {
+    Larger(u64),
+    Smaller(u64),
+}
+
+impl SizeFilter {
+    fn parse(t: &str) -> Option<Self> {
+        let size = t.strip_prefix("size:")?;
+        let (larger, size) = match size.split_at_checked(1)? {
+            (">", s) => (true, s),
+            ("<", s) => (false, s),
+            _ => return None,
+        };
+        let unit_start = size
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(size.len());
+        let (number, unit) = size.split_at(unit_start);
+        let unit = match unit {
+            "" | "b" => 1,
+            "kb" => 1 << 10,
+            "mb" => 1 << 20,
+            "gb" => 1 << 30,
+            _ => return None,
+        };
+        let bytes = (number.parse::<f64>().ok()? * unit as f64) as u64;
+        Some(if larger {
+            Self::Larger(bytes)
+        } else {
+            Self::Smaller(bytes)
+        })
+    }
+
+    // documents with an unknown size never match
+    fn matches(self, size: u64) -> bool {
+        match self {
+            Self::Larger(b) => size != 0 && size > b,
+            Self::Smaller(b) => size != 0 && size < b,
+        }
+    }
+}
+
+// the query terms that only narrow down which documents are searched
+#[derive(Debug, Clone, Copy)]
+enum Filter {
+    Date(DateFilter),
+    Size(SizeFilter),
+}
+
+impl Filter {
+    fn parse(t: &str) -> Option<Self> {
+        DateFilter::parse(t)
+            .map(Self::Date)
+            .or_else(|| SizeFilter::parse(t).map(Self::Size))
+    }
+
+    fn matches(self, d: &Document) -> bool {
+        match self {
+            Self::Date(f) => f.matches(d.modified),
+            Self::Size(f) => f.matches(d.size),
+        }
+    }
+}
+
+/// Finds the byte ranges of the words in `text` that share a stem with any of the query terms, so
+/// "running" gets highlighted when searching for "run"
+pub fn highlight_ranges(
+    text: &str,
+    terms: &[&str],
+    mode: QueryMode,
+) -> Vec<std::ops::Range<usize>> {
+    let stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
+    let (prefixes, terms): (Vec<&str>, Vec<&str>) = terms.iter().partition(|t| t.ends_with('*'));
+    let prefixes: Vec<_> = prefixes
+        .iter()
+        .map(|p| mode.fold_case(p.trim_end_matches('*')))
+        .collect();
+    let normalized: HashSet<String> = terms.iter().map(|t| mode.normalize(&stemmer, t)).collect();
+    let mut ranges = vec![];
+    let mut word_start = None;
+    // the trailing space flushes the last word
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if is_word_char(c) {
+            word_start.get_or_insert(i);
+        } else if let Some(start) = word_start.take() {
+            let word = mode.fold_case(&text[start..i]);
+            if normalized.contains(&mode.normalize(&stemmer, &word))
+                || prefixes.iter().any(|p| word.starts_with(p.as_str()))
+            {
+                ranges.push(start..i);
+            }
+        }
+    }
+    ranges
+}
+
+/// The first match of `terms` in `text` (see `highlight_ranges`) with up to `words` words around
+/// it, `None` if none of them is in it. The text gets tokenized again for it, so it works without
+/// `IndexOptions::store_positions`
+pub fn snippet(text: &str, terms: &[&str], mode: QueryMode, words: usize) -> Option<String> {
+    let first = highlight_ranges(text, terms, mode).into_iter().next()?;
+    // from the start of the whitespace separated word the match is in, it might start with `(`
+    let start = text[..first.start]
+        .trim_end_matches(|c: char| !c.is_whitespace())
+        .len();
+    let mut parts: Vec<&str> = text[..start]
+        .split_whitespace()
+        .rev()
+        .take(words / 2)
+        .collect();
+    parts.reverse();
+    let before = parts.len();
+    let mut after = text[start..].split_whitespace();
+    parts.extend(after.by_ref().take(words - before + 1));
+    let mut snippet = parts.join(" ");
+    if text[..start].split_whitespace().nth(before).is_some() {
+        snippet.insert_str(0, "... ");
+    }
+    if after.next().is_some() {
+        snippet.push_str(" ...");
+    }
+    Some(snippet)
+}
+
+/// Separates the path of an archive from the path of an entry inside of it
+/// (e.g. `bundle.zip!/notes/doc.xml`)
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SchemaRead, SchemaWrite, Serialize, Deserialize)]
+pub enum FileType {
     Xml,
     Pdf,
+    Zip,
+    Email,
+    Mbox,
+    Txt,
+    Subtitle,
+    Markdown,
 }
 
-impl FromStr for FileType {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "xml" | "xhtml" => Ok(Self::Xml),
-            "pdf" => Ok(Self::Pdf),
-            x => {
-                eprintln!("[ERR]: File is of unindexable type {x}");
-                Err(())
-            }
+/// Reads the text out of one file format, every format that gets indexed has one in `EXTRACTORS`
+pub trait TextExtractor: Sync {
+    /// Extensions (without the dot) of the files it reads
+    fn extensions(&self) -> &[&str];
+
+    /// What the documents it reads get marked as, `None` for formats without their own type
+    fn file_type(&self) -> Option<FileType> {
+        None
+    }
+
+    /// The plain text of a document, with the text of its fields kept apart so they can be boosted
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()>;
+
+    /// Indexes the extracted text, formats that can be tokenized while they're being read do that
+    /// instead
+    fn extract_document(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<Document, ()> {
+        let extracted = self.extract(r, name, opts)?;
+        let mut document = create_document_from_text(&extracted.body, opts);
+        for (field, text) in &extracted.fields {
+            document.add_field(field, text, opts);
         }
+        Ok(document)
     }
 }
 
-fn analyze_file(p: &std::path::Path) -> Result<(String, Document), ()> {
-    match p.extension() {
-        None => {
-            eprintln!("[ERR]: File is binary or other type of non-indexable file");
-            return Err(());
+// NOTE: A new format only needs a `TextExtractor` added here, archives (.zip, .mbox) are handled on
+// their own since their entries are documents of any of these formats
+static EXTRACTORS: &[&dyn TextExtractor] = &[
+    &XmlExtractor,
+    &TxtExtractor,
+    &PdfExtractor,
+    &EmailExtractor,
+    &SubtitleExtractor,
+    &MarkdownExtractor,
+];
+
+fn extractor_for(extension: &str) -> Option<&'static dyn TextExtractor> {
+    EXTRACTORS
+        .iter()
+        .find(|e| e.extensions().contains(&extension))
+        .copied()
+}
+
+// the extractor for a path by its extension
+fn extractor_for_path(p: &str) -> Option<&'static dyn TextExtractor> {
+    extractor_for(std::path::Path::new(p).extension()?.to_str()?)
+}
+
+// `name` is only used for diagnostics since the reader might not be an actual file (archive entries)
+fn extract_document<R: std::io::Read>(
+    extractor: &dyn TextExtractor,
+    r: R,
+    name: &str,
+    opts: &IndexOptions,
+) -> Result<Document, ()> {
+    let mut r = HashingReader {
+        inner: r,
+        hash: FNV_OFFSET_BASIS,
+    };
+    let mut document = extractor.extract_document(&mut r, name, opts)?;
+    // whatever the extraction didn't read still counts for the hash
+    if let Err(e) = std::io::copy(&mut r, &mut std::io::sink()) {
+        log::error!("Failed to read {name}: {e}");
+        return Err(());
+    }
+    document.file_type = extractor.file_type();
+    document.content_hash = r.hash;
+    Ok(document)
+}
+
+// 64 bit FNV-1a, unlike `DefaultHasher` it's guaranteed to stay the same between builds so the
+// hashes stored in the index stay comparable
+// see: http://www.isthe.com/chongo/tech/comp/fnv/
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+fn content_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |h, b| (h ^ u64::from(*b)).wrapping_mul(0x100000001b3))
+}
+
+// hashes the bytes as they get read, so the file doesn't have to be read into memory for it
+struct HashingReader<R> {
+    inner: R,
+    hash: u64,
+}
+
+impl<R: std::io::Read> std::io::Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hash = content_hash(self.hash, &buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Drops every document whose file (or archive) no longer exists, returns how many got dropped
+pub fn remove_missing(model: &mut HashMap<String, Document>) -> usize {
+    let before = model.len();
+    model.retain(|p, _| {
+        let file = p
+            .split_once(ARCHIVE_SEPARATOR)
+            .map_or(p.as_str(), |(a, _)| a);
+        std::path::Path::new(file).exists()
+    });
+    before - model.len()
+}
+
+/// Drops every document whose file content is the same as that of another one (the one with the
+/// smallest path is kept), returns how many got dropped
+pub fn remove_duplicates(model: &mut HashMap<String, Document>) -> usize {
+    let mut paths: Vec<String> = model.keys().cloned().collect();
+    paths.sort_unstable();
+    let mut seen = HashSet::new();
+    let mut removed = 0;
+    for p in paths {
+        let hash = model[&p].content_hash;
+        if hash != 0 && !seen.insert(hash) {
+            model.remove(&p);
+            removed += 1;
         }
-        Some(s) => match s.to_str().unwrap().parse() {
-            Ok(FileType::Xml) => {
-                let file = BufReader::new(std::fs::File::open(p).unwrap());
-                let parser = xml::EventReader::new(file);
-                let mut text = String::with_capacity(1024 * 1024);
-                for e in parser {
-                    match e {
-                        Ok(xml::reader::XmlEvent::Characters(c)) => {
-                            text.push_str(&c);
-                            text.push(' ');
-                        }
-                        Err(e) => {
-                            eprintln!("{}", e);
-                        }
-                        _ => {}
-                    }
+    }
+    removed
+}
+
+// goes through the text of an xml document piece by piece, the flag is set for the pieces that are
+// also captions (image alt texts and <figcaption>s, they describe the figures)
+fn parse_xml<R: std::io::Read>(r: R, opts: &IndexOptions, mut on_text: impl FnMut(&str, bool)) {
+    let parser = xml::EventReader::new(BufReader::new(r));
+    let mut figcaption_depth = 0usize;
+    for e in parser {
+        match e {
+            Ok(xml::reader::XmlEvent::Characters(c)) => on_text(&c, figcaption_depth > 0),
+            Ok(xml::reader::XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let is_img = name.local_name.eq_ignore_ascii_case("img");
+                let is_alt = |a: &xml::attribute::OwnedAttribute| {
+                    is_img && a.name.local_name.eq_ignore_ascii_case("alt")
+                };
+                if name.local_name.eq_ignore_ascii_case("figcaption") {
+                    figcaption_depth += 1;
+                } else if let Some(alt) = attributes.iter().find(|a| is_alt(a)) {
+                    on_text(&alt.value, true);
                 }
-                Ok((
-                    p.to_string_lossy().to_string(),
-                    create_document_from_text(&text),
-                ))
-            }
-            Ok(FileType::Pdf) => {
-                let doc = lopdf::Document::load(&p).unwrap();
-                if doc.is_encrypted() {
-                    eprintln!("[WARN]: Skipping encrypted .pdf file {}", p.display());
-                    return Err(());
+                if opts.xml_attributes {
+                    // the alt texts are in there already
+                    for a in attributes.iter().filter(|a| !is_alt(a)) {
+                        on_text(&a.value, false);
+                    }
                 }
-                let page_nums: Vec<u32> = doc.get_pages().into_keys().collect();
-                let text = doc.extract_text(&page_nums).unwrap();
-                Ok((
-                    p.to_string_lossy().to_string(),
-                    create_document_from_text(&text),
-                ))
             }
-            Err(()) => {
-                eprintln!("Ignoring binary file");
-                Err(())
+            Ok(xml::reader::XmlEvent::EndElement { name })
+                if name.local_name.eq_ignore_ascii_case("figcaption") =>
+            {
+                figcaption_depth = figcaption_depth.saturating_sub(1);
+            }
+            Err(e) => {
+                log::warn!("{e}");
             }
-        },
+            _ => {}
+        }
     }
 }
 
-pub fn analyze_dir(p: &std::path::Path) -> Result<HashMap<String, Document>, ()> {
-    let mut map = HashMap::new();
-    let mut on_going = vec![];
-    for d in p.read_dir().unwrap() {
-        let d = d.unwrap();
-        if d.metadata().unwrap().is_file() {
-            let Ok((p, f)) = analyze_file(&d.path()) else {
-                continue;
-            };
-            map.insert(p, f);
-        } else {
-            let process = std::thread::spawn(move || analyze_dir(&d.path()));
-            on_going.push(process);
+// how much of a text file the encoding is guessed from
+const ENCODING_SNIFF_LEN: u64 = 64 * 1024;
+
+// a byte order mark wins, otherwise it's guessed from the bytes (utf-8 if they're valid utf-8)
+fn detect_encoding(prefix: &[u8], complete: bool) -> &'static encoding_rs::Encoding {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(prefix) {
+        return encoding;
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(prefix, complete);
+    detector.guess(None, true)
+}
+
+// reads a line at a time so only the current line has to be in memory, text in other encodings
+// (latin-1, utf-16, ...) gets converted to utf-8 and anything invalid gets replaced
+fn read_text_lines<R: std::io::Read>(
+    mut r: R,
+    name: &str,
+    mut on_line: impl FnMut(&str),
+) -> Result<(), ()> {
+    let mut prefix = vec![];
+    if let Err(e) = std::io::Read::read_to_end(
+        &mut std::io::Read::take(&mut r, ENCODING_SNIFF_LEN),
+        &mut prefix,
+    ) {
+        log::error!("Failed to read {name}: {e}");
+        return Err(());
+    }
+    let encoding = detect_encoding(&prefix, (prefix.len() as u64) < ENCODING_SNIFF_LEN);
+    if encoding != encoding_rs::UTF_8 {
+        log::debug!("Reading {name} as {}", encoding.name());
+    }
+    let decoder = encoding_rs_io::DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .build(std::io::Read::chain(prefix.as_slice(), r));
+    let mut r = BufReader::new(decoder);
+    let mut line = vec![];
+    loop {
+        line.clear();
+        match std::io::BufRead::read_until(&mut r, b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => on_line(&String::from_utf8_lossy(&line)),
+            Err(e) => {
+                log::error!("Failed to read {name}: {e}");
+                return Err(());
+            }
         }
     }
-    for p in on_going {
-        let x = p.join().unwrap().unwrap();
-        x.into_iter().for_each(|(k, v)| {
-            map.insert(k, v);
+}
+
+/// The plain text of a document, with the text of its fields kept apart so they can be boosted
+pub struct ExtractedText {
+    pub body: String,
+    /// field name (see `field_boost`) and its text
+    pub fields: Vec<(String, String)>,
+}
+
+struct XmlExtractor;
+
+impl TextExtractor for XmlExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["xml", "xhtml"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Xml)
+    }
+
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        _name: &str,
+        opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let mut text = String::new();
+        let mut captions = String::new();
+        parse_xml(r, opts, |t, caption| {
+            text.push_str(t);
+            text.push(' ');
+            if caption {
+                captions.push_str(t);
+                captions.push(' ');
+            }
+        });
+        Ok(ExtractedText {
+            body: text,
+            fields: vec![(FIELD_CAPTION.to_string(), captions)],
+        })
+    }
+
+    // NOTE: Tokenized while it's being read instead of collecting the text first
+    fn extract_document(
+        &self,
+        r: &mut dyn std::io::Read,
+        _name: &str,
+        opts: &IndexOptions,
+    ) -> Result<Document, ()> {
+        let mut body = Tokenizer::new(opts);
+        let mut captions = Tokenizer::new(opts);
+        parse_xml(r, opts, |text, caption| {
+            body.push_str(text);
+            body.push(' ');
+            if caption {
+                captions.push_str(text);
+                captions.push(' ');
+            }
         });
+        let mut document = create_document_from_field(body.finish());
+        document.insert_field(FIELD_CAPTION, captions.finish());
+        Ok(document)
     }
-    Ok(map)
 }
 
-#[derive(Debug, SchemaRead, SchemaWrite)]
-pub struct Document {
-    words: HashMap<String, usize>,
+struct TxtExtractor;
+
+impl TextExtractor for TxtExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["txt"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Txt)
+    }
+
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let mut text = String::new();
+        let mut fields: Vec<(String, String)> = vec![];
+        read_text_lines(r, name, |line| match tagged_line(line, opts) {
+            Some((tag, value)) => match fields.iter_mut().find(|(t, _)| *t == tag) {
+                Some((_, field)) => field.push_str(value),
+                None => fields.push((tag, value.to_string())),
+            },
+            None => text.push_str(line),
+        })?;
+        Ok(ExtractedText { body: text, fields })
+    }
+
+    // NOTE: Tokenized while it's being read instead of collecting the text first
+    fn extract_document(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<Document, ()> {
+        let mut body = Tokenizer::new(opts);
+        let mut fields: HashMap<String, Tokenizer> = HashMap::new();
+        read_text_lines(r, name, |line| match tagged_line(line, opts) {
+            Some((tag, value)) => fields
+                .entry(tag)
+                .or_insert_with(|| Tokenizer::new(opts))
+                .push_str(value),
+            None => body.push_str(line),
+        })?;
+        let mut document = create_document_from_field(body.finish());
+        for (tag, field) in fields {
+            document.insert_field(&tag, field.finish());
+        }
+        Ok(document)
+    }
 }
 
-pub fn do_query(model: &HashMap<String, Document>, terms: &[&str]) -> Vec<String> {
-    let en_stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
-    let mut docs = vec![];
-    for (path, data) in model {
-        let mut point = 0.0;
-        for t in terms {
-            let t = en_stemmer.stem(&t.to_lowercase()).to_string();
-            let count = match data.words.get(&t) {
-                None => {
-                    continue;
+// splits a line like `Title: some text` into the (lowercased) tag and its text, if the tag is one
+// of `IndexOptions::field_tags`
+fn tagged_line<'a>(line: &'a str, opts: &IndexOptions) -> Option<(String, &'a str)> {
+    if opts.field_tags.is_empty() {
+        return None;
+    }
+    let (tag, value) = line.trim_start().split_once(':')?;
+    let tag = tag.trim_end().to_lowercase();
+    opts.field_tags.contains(&tag).then_some((tag, value))
+}
+
+struct PdfExtractor;
+
+impl TextExtractor for PdfExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Pdf)
+    }
+
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let doc = match lopdf::Document::load_from(r) {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("Failed to load .pdf file {name}: {e}");
+                return Err(());
+            }
+        };
+        if doc.is_encrypted() {
+            log::warn!("Skipping encrypted .pdf file {name}");
+            return Err(());
+        }
+        let pages = doc.get_pages();
+        let page_nums: Vec<u32> = pages.keys().copied().collect();
+        let text = match doc.extract_text(&page_nums) {
+            Ok(t) => t,
+            Err(e) => {
+                log::error!("Failed to extract the text of .pdf file {name}: {e}");
+                return Err(());
+            }
+        };
+        let mut fields = vec![];
+        // not every pdf has an outline, so failing to read one is fine
+        if let Ok(toc) = doc.get_toc() {
+            let titles: Vec<_> = toc.toc.into_iter().map(|e| e.title).collect();
+            fields.push((FIELD_OUTLINE.to_string(), titles.join("\n")));
+        }
+        if opts.pdf_annotations {
+            let notes = pdf_annotation_notes(&doc, pages.into_values());
+            fields.push((FIELD_ANNOTATION.to_string(), notes));
+        }
+        Ok(ExtractedText { body: text, fields })
+    }
+}
+
+// the notes written on the annotations of the pages (sticky notes, comments on highlights...),
+// links, form fields and popups (which repeat the note of their annotation) are left out
+fn pdf_annotation_notes(
+    doc: &lopdf::Document,
+    pages: impl Iterator<Item = lopdf::ObjectId>,
+) -> String {
+    let mut notes = String::new();
+    for page in pages {
+        let Ok(annotations) = doc.get_page_annotations(page) else {
+            continue;
+        };
+        for a in annotations {
+            let subtype = a.get(b"Subtype").and_then(|s| s.as_name());
+            if matches!(subtype, Ok(b"Link" | b"Widget" | b"Popup")) {
+                continue;
+            }
+            if let Ok(note) = a
+                .get(b"Contents")
+                .and_then(|c| doc.dereference(c))
+                .and_then(|(_, c)| lopdf::decode_text_string(c))
+            {
+                notes.push_str(&note);
+                notes.push('\n');
+            }
+        }
+    }
+    notes
+}
+
+struct EmailExtractor;
+
+impl TextExtractor for EmailExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["eml"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Email)
+    }
+
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        _opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let mut raw = vec![];
+        if let Err(e) = r.read_to_end(&mut raw) {
+            log::error!("Failed to read email {name}: {e}");
+            return Err(());
+        }
+        let Some(msg) = mail_parser::MessageParser::default().parse(&raw) else {
+            log::error!("Failed to parse email {name}");
+            return Err(());
+        };
+        let mut text = String::new();
+        for addresses in [msg.from(), msg.to(), msg.cc()].into_iter().flatten() {
+            for a in addresses.iter() {
+                for part in a.name().into_iter().chain(a.address()) {
+                    text.push_str(part);
+                    text.push(' ');
                 }
-                Some(t) => *t,
-            };
-            let tf = count as f64 / data.words.values().copied().sum::<usize>() as f64;
-            let idf = (model.iter().count() as f64
-                / model
-                    .iter()
-                    .filter(|(_, d)| d.words.contains_key(&t))
-                    .count() as f64)
-                .log2();
-            point += tf * idf;
-        }
-        docs.push((path, point));
-    }
-    docs.sort_by(|(_, b1), (_, a1)| a1.total_cmp(b1));
-    docs.iter()
-        .map(|(p, d)| (p, d))
-        .filter(|(_p, d)| **d != 0.0)
-        .map(|(p, _)| p.to_owned().clone())
-        .collect()
+            }
+        }
+        // only the text parts, attachments are left out
+        for i in 0..msg.text_body_count() {
+            if let Some(body) = msg.body_text(i) {
+                text.push_str(&body);
+                text.push('\n');
+            }
+        }
+        let fields = msg
+            .subject()
+            .map(|subject| (FIELD_SUBJECT.to_string(), subject.to_string()))
+            .into_iter()
+            .collect();
+        Ok(ExtractedText { body: text, fields })
+    }
+}
+
+struct SubtitleExtractor;
+
+impl TextExtractor for SubtitleExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["srt", "vtt"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Subtitle)
+    }
+
+    // the spoken text with the time every cue starts at in front of it (e.g. `[00:14:32] ...`)
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        _opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let mut text = String::new();
+        let mut parser = SubtitleParser::default();
+        let mut cue_start = None;
+        read_text_lines(r, name, |line| match parser.line(line) {
+            SubtitleLine::Start(start) => cue_start = Some(start.to_string()),
+            SubtitleLine::Text(t) => {
+                if let Some(start) = cue_start.take() {
+                    text.push_str(&format!("[{start}] "));
+                }
+                text.push_str(&t);
+                text.push('\n');
+            }
+            SubtitleLine::Skip => {}
+        })?;
+        Ok(ExtractedText {
+            body: text,
+            fields: vec![],
+        })
+    }
+
+    // NOTE: Only the spoken text gets indexed, the timestamps would just be noise terms
+    fn extract_document(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<Document, ()> {
+        let mut body = Tokenizer::new(opts);
+        let mut parser = SubtitleParser::default();
+        read_text_lines(r, name, |line| {
+            if let SubtitleLine::Text(t) = parser.line(line) {
+                body.push_str(&t);
+                body.push_str("\n");
+            }
+        })?;
+        Ok(create_document_from_field(body.finish()))
+    }
+}
+
+// what a line of a .srt/.vtt file is
+enum SubtitleLine<'a> {
+    // the timing line of a cue, with the time it starts at without the milliseconds (`00:14:32`
+    // out of `00:14:32,000 --> 00:14:35,500`)
+    Start(&'a str),
+    // spoken text without the formatting tags
+    Text(String),
+    // cue numbers, the vtt header and its note/style/region blocks
+    Skip,
+}
+
+#[derive(Default)]
+struct SubtitleParser {
+    // inside of a block that isn't spoken text, it ends at the next blank line
+    skipping_block: bool,
+}
+
+impl SubtitleParser {
+    fn line<'a>(&mut self, line: &'a str) -> SubtitleLine<'a> {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() {
+            self.skipping_block = false;
+            return SubtitleLine::Skip;
+        }
+        if self.skipping_block {
+            return SubtitleLine::Skip;
+        }
+        let block = line.split_whitespace().next().unwrap_or_default();
+        if matches!(block, "WEBVTT" | "NOTE" | "STYLE" | "REGION") {
+            self.skipping_block = true;
+            return SubtitleLine::Skip;
+        }
+        if let Some((start, _)) = line.split_once("-->") {
+            let start = start.trim();
+            return SubtitleLine::Start(start.split(['.', ',']).next().unwrap_or(start));
+        }
+        if line.chars().all(|c| c.is_ascii_digit()) {
+            return SubtitleLine::Skip;
+        }
+        SubtitleLine::Text(strip_subtitle_tags(line))
+    }
+}
+
+// drops `<i>`/`<c.yellow>` style tags and `{\an8}` positioning codes
+fn strip_subtitle_tags(line: &str) -> String {
+    let mut text = String::new();
+    let mut closing = None;
+    for c in line.chars() {
+        match (closing, c) {
+            (None, '<') => closing = Some('>'),
+            (None, '{') => closing = Some('}'),
+            (None, c) => text.push(c),
+            (Some(end), c) if c == end => closing = None,
+            _ => {}
+        }
+    }
+    text
+}
+
+struct MarkdownExtractor;
+
+impl TextExtractor for MarkdownExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["md", "markdown"]
+    }
+
+    fn file_type(&self) -> Option<FileType> {
+        Some(FileType::Markdown)
+    }
+
+    fn extract(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        _opts: &IndexOptions,
+    ) -> Result<ExtractedText, ()> {
+        let mut text = String::new();
+        read_text_lines(r, name, |line| text.push_str(&strip_markdown_line(line)))?;
+        Ok(ExtractedText {
+            body: text,
+            fields: vec![],
+        })
+    }
+
+    fn extract_document(
+        &self,
+        r: &mut dyn std::io::Read,
+        name: &str,
+        opts: &IndexOptions,
+    ) -> Result<Document, ()> {
+        let mut body = Tokenizer::new(opts);
+        read_text_lines(r, name, |line| body.push_str(&strip_markdown_line(line)))?;
+        Ok(create_document_from_field(body.finish()))
+    }
+}
+
+// drops the markdown syntax of a line so the `#`s and `*`s don't end up as terms, links and images
+// keep just their text (`[the docs](https://...)` -> "the docs")
+fn strip_markdown_line(line: &str) -> String {
+    let content = line.trim();
+    // horizontal rules and the underlines of headings
+    if !content.is_empty()
+        && content
+            .chars()
+            .all(|c| matches!(c, '-' | '*' | '_' | '=' | ' '))
+    {
+        return "\n".to_string();
+    }
+    // heading, quote and list markers
+    let content = content.trim_start_matches(['#', '>']).trim_start();
+    let content = match content.strip_prefix(['-', '*', '+']) {
+        Some(rest) if rest.starts_with(' ') => rest,
+        _ => content,
+    };
+    let mut text = String::new();
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '`' | '~' | '[' => {}
+            '!' if chars.peek() == Some(&'[') => {}
+            // `_emphasis_`, `snake_case` still gets split into its words
+            '_' => text.push(' '),
+            ']' if chars.peek() == Some(&'(') => {
+                // the url of the link
+                for c in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            ']' => {}
+            c => text.push(c),
+        }
+    }
+    text.push('\n');
+    text
+}
+
+/// Why a file or a directory couldn't be indexed, along with the path it happened at
+#[derive(Debug)]
+pub struct IndexError {
+    pub path: std::path::PathBuf,
+    pub kind: IndexErrorKind,
+}
+
+#[derive(Debug)]
+pub enum IndexErrorKind {
+    ReadDir(std::io::Error),
+    Metadata(std::io::Error),
+    Open(std::io::Error),
+    /// the extractor logs what exactly went wrong
+    Extract,
+    /// one of `IndexOptions::excluded_files`
+    Excluded,
+    /// a format none of the extractors read
+    Unsupported,
+    Panicked,
+}
+
+impl IndexError {
+    fn new(path: &std::path::Path, kind: IndexErrorKind) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind,
+        }
+    }
+
+    /// Excluded files and the ones of unsupported formats are left out on purpose
+    pub fn is_skipped(&self) -> bool {
+        matches!(
+            self.kind,
+            IndexErrorKind::Excluded | IndexErrorKind::Unsupported
+        )
+    }
+}
+
+impl std::fmt::Display for IndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let p = self.path.display();
+        match &self.kind {
+            IndexErrorKind::ReadDir(e) => write!(f, "failed to read the directory {p}: {e}"),
+            IndexErrorKind::Metadata(e) => write!(f, "failed to read the metadata of {p}: {e}"),
+            IndexErrorKind::Open(e) => write!(f, "failed to open {p}: {e}"),
+            IndexErrorKind::Extract => write!(f, "failed to extract the text of {p}"),
+            IndexErrorKind::Excluded => write!(f, "{p} is excluded"),
+            IndexErrorKind::Unsupported => write!(f, "{p} isn't a supported format"),
+            IndexErrorKind::Panicked => write!(f, "indexing {p} panicked"),
+        }
+    }
+}
+
+fn open_zip(p: &std::path::Path) -> Result<zip::ZipArchive<BufReader<std::fs::File>>, ()> {
+    let file = match std::fs::File::open(p) {
+        Ok(f) => f,
+        Err(e) => {
+            log::error!("Failed to open archive {}: {e}", p.display());
+            return Err(());
+        }
+    };
+    zip::ZipArchive::new(BufReader::new(file)).map_err(|e| {
+        log::error!("Failed to read archive {}: {e}", p.display());
+    })
+}
+
+// every supported entry gets indexed under `<archive path>!/<entry path>`
+fn analyze_zip(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<Vec<(String, Document)>, IndexError> {
+    let file = std::fs::File::open(p).map_err(|e| IndexError::new(p, IndexErrorKind::Open(e)))?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(|e| {
+        log::error!("Failed to read archive {}: {e}", p.display());
+        IndexError::new(p, IndexErrorKind::Extract)
+    })?;
+    let mut docs = vec![];
+    for i in 0..archive.len() {
+        let entry = match archive.by_index(i) {
+            Ok(e) => e,
+            Err(e) => {
+                log::warn!("Skipping unreadable entry in {}: {e}", p.display());
+                continue;
+            }
+        };
+        if !entry.is_file() {
+            continue;
+        }
+        let Ok(entry_name) = entry.name().map(|n| n.to_string()) else {
+            log::warn!("Skipping entry with a malformed name in {}", p.display());
+            continue;
+        };
+        let name = format!("{}{ARCHIVE_SEPARATOR}{entry_name}", p.display());
+        // nested archives and unknown formats are left out
+        let Some(extractor) = extractor_for_path(&entry_name) else {
+            continue;
+        };
+        if let Ok(d) = extract_document(extractor, entry, &name, opts) {
+            docs.push((name, d));
+        }
+    }
+    Ok(docs)
+}
+
+fn open_mbox(
+    p: &std::path::Path,
+) -> Result<mail_parser::mailbox::mbox::MessageIterator<BufReader<std::fs::File>>, ()> {
+    match std::fs::File::open(p) {
+        Ok(f) => Ok(mail_parser::mailbox::mbox::MessageIterator::new(
+            BufReader::new(f),
+        )),
+        Err(e) => {
+            log::error!("Failed to open mailbox {}: {e}", p.display());
+            Err(())
+        }
+    }
+}
+
+// every message gets indexed under `<mbox path>!/<message number>.eml`
+fn analyze_mbox(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<Vec<(String, Document)>, IndexError> {
+    let file = std::fs::File::open(p).map_err(|e| IndexError::new(p, IndexErrorKind::Open(e)))?;
+    let messages = mail_parser::mailbox::mbox::MessageIterator::new(BufReader::new(file));
+    let mut docs = vec![];
+    for (i, msg) in messages.enumerate() {
+        let msg = match msg {
+            Ok(m) => m,
+            Err(e) => {
+                log::error!("Failed to read mailbox {}: {e}", p.display());
+                break;
+            }
+        };
+        let name = format!("{}{ARCHIVE_SEPARATOR}{i}.eml", p.display());
+        if let Ok(d) = extract_document(&EmailExtractor, msg.contents(), &name, opts) {
+            docs.push((name, d));
+        }
+    }
+    Ok(docs)
+}
+
+fn analyze_file(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<Vec<(String, Document)>, IndexError> {
+    if opts.excluded_files.contains(p) {
+        return Err(IndexError::new(p, IndexErrorKind::Excluded));
+    }
+    let mut docs = analyze_file_contents(p, opts)?;
+    let (modified, size) = std::fs::metadata(p).map_or((0, 0), |m| file_stamp(&m));
+    for (k, d) in &mut docs {
+        d.modified = modified;
+        d.size = size;
+        if opts.index_dates && modified != 0 {
+            d.add_date_terms();
+        }
+        if opts.log_extracted_chars {
+            match d.indexed_chars() {
+                // e.g. a pdf that's only scanned images
+                0 => log::warn!("No text extracted from {k}"),
+                n => log::info!("Extracted {n} characters from {k}"),
+            }
+        }
+        if opts.path_terms {
+            d.add_field(FIELD_PATH, &path_words(k), opts);
+        }
+    }
+    Ok(docs)
+}
+
+fn analyze_file_contents(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<Vec<(String, Document)>, IndexError> {
+    let Some(extension) = p.extension().and_then(|e| e.to_str()) else {
+        return Err(IndexError::new(p, IndexErrorKind::Unsupported));
+    };
+    match extension {
+        "zip" => analyze_zip(p, opts),
+        "mbox" => analyze_mbox(p, opts),
+        x => {
+            let Some(extractor) = extractor_for(x) else {
+                return Err(IndexError::new(p, IndexErrorKind::Unsupported));
+            };
+            let file =
+                std::fs::File::open(p).map_err(|e| IndexError::new(p, IndexErrorKind::Open(e)))?;
+            let name = p.to_string_lossy().to_string();
+            let doc = extract_document(extractor, file, &name, opts)
+                .map_err(|()| IndexError::new(p, IndexErrorKind::Extract))?;
+            Ok(vec![(name, doc)])
+        }
+    }
+}
+
+// where archive entries get extracted to so they can be opened
+fn extraction_path(
+    archive_path: &std::path::Path,
+    rel: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    let out = std::env::temp_dir()
+        .join("local-search")
+        .join(archive_path.file_name()?)
+        .join(rel);
+    if let Some(parent) = out.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        log::error!("Failed to create {}: {e}", parent.display());
+        return None;
+    }
+    Some(out)
+}
+
+fn extract_mbox_message(archive_path: &std::path::Path, inner: &str) -> Option<std::path::PathBuf> {
+    let Some(i) = inner
+        .strip_suffix(".eml")
+        .and_then(|i| i.parse::<usize>().ok())
+    else {
+        log::error!("{inner} is not a message in {}", archive_path.display());
+        return None;
+    };
+    let Some(Ok(msg)) = open_mbox(archive_path).ok()?.nth(i) else {
+        log::error!("Failed to find {inner} in {}", archive_path.display());
+        return None;
+    };
+    let out = extraction_path(archive_path, std::path::Path::new(inner))?;
+    if let Err(e) = std::fs::write(&out, msg.contents()) {
+        log::error!("Failed to extract {inner} to {}: {e}", out.display());
+        return None;
+    }
+    Some(out)
+}
+
+/// Turns a document path from the model into a file that can be opened, archive entries get
+/// extracted into the temp directory first
+pub fn resolve_document_path(p: &str) -> Option<std::path::PathBuf> {
+    let Some((archive_path, inner)) = p.split_once(ARCHIVE_SEPARATOR) else {
+        return Some(std::path::PathBuf::from(p));
+    };
+    let archive_path = std::path::Path::new(archive_path);
+    if archive_path.extension().is_some_and(|e| e == "mbox") {
+        return extract_mbox_message(archive_path, inner);
+    }
+    let mut archive = open_zip(archive_path).ok()?;
+    let mut entry = match archive.by_name(inner) {
+        Ok(e) => e,
+        Err(e) => {
+            log::error!("Failed to find {inner} in {}: {e}", archive_path.display());
+            return None;
+        }
+    };
+    let Some(rel) = entry.enclosed_name() else {
+        log::error!("Refusing to extract {inner}, it points outside of the archive");
+        return None;
+    };
+    let out = extraction_path(archive_path, &rel)?;
+    let extracted = std::fs::File::create(&out).and_then(|mut f| std::io::copy(&mut entry, &mut f));
+    if let Err(e) = extracted {
+        log::error!("Failed to extract {inner} to {}: {e}", out.display());
+        return None;
+    }
+    Some(out)
+}
+
+// the raw bytes of a document from the model, archive entries are read straight from the archive
+fn read_document_bytes(p: &str) -> Option<Vec<u8>> {
+    let Some((archive_path, inner)) = p.split_once(ARCHIVE_SEPARATOR) else {
+        return std::fs::read(p)
+            .map_err(|e| log::error!("Failed to read {p}: {e}"))
+            .ok();
+    };
+    let archive_path = std::path::Path::new(archive_path);
+    if archive_path.extension().is_some_and(|e| e == "mbox") {
+        let i: usize = inner.strip_suffix(".eml")?.parse().ok()?;
+        let Some(Ok(msg)) = open_mbox(archive_path).ok()?.nth(i) else {
+            log::error!("Failed to find {inner} in {}", archive_path.display());
+            return None;
+        };
+        return Some(msg.contents().to_vec());
+    }
+    let mut archive = open_zip(archive_path).ok()?;
+    let mut entry = match archive.by_name(inner) {
+        Ok(e) => e,
+        Err(e) => {
+            log::error!("Failed to find {inner} in {}: {e}", archive_path.display());
+            return None;
+        }
+    };
+    let mut bytes = vec![];
+    if let Err(e) = std::io::Read::read_to_end(&mut entry, &mut bytes) {
+        log::error!("Failed to read {p}: {e}");
+        return None;
+    }
+    Some(bytes)
+}
+
+/// Extracts the plain text of a document from the model again (e.g. to preview it), `None` if the
+/// file is gone or can't be read
+pub fn read_document_text(p: &str) -> Option<String> {
+    let extractor = extractor_for_path(p)?;
+    let bytes = read_document_bytes(p)?;
+    extractor
+        .extract(&mut bytes.as_slice(), p, &IndexOptions::default())
+        .ok()
+        .map(|extracted| extracted.body)
+}
+
+// modification time of the file a document comes from (the archive for archive entries)
+fn document_file_modified(p: &str) -> Option<std::time::SystemTime> {
+    let file = p.split_once(ARCHIVE_SEPARATOR).map_or(p, |(a, _)| a);
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+struct CachedText {
+    path: String,
+    modified: Option<std::time::SystemTime>,
+    text: Option<String>,
+}
+
+/// The text of the last few documents that got read again (for previews), so going back and forth
+/// between results doesn't extract the same files over and over. Entries are dropped least recently
+/// used first, or once their file changes
+pub struct TextCache {
+    capacity: usize,
+    // least recently used first
+    entries: std::collections::VecDeque<CachedText>,
+}
+
+impl TextCache {
+    /// Keeps at least the document that was read last
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Same as `read_document_text`, just cached
+    pub fn text(&mut self, p: &str) -> Option<&str> {
+        let modified = document_file_modified(p);
+        let entry = match self.entries.iter().position(|e| e.path == p) {
+            Some(i) => self.entries.remove(i)?,
+            None => CachedText {
+                path: p.to_string(),
+                modified: None,
+                text: None,
+            },
+        };
+        let entry = if entry.text.is_some() && entry.modified == modified {
+            entry
+        } else {
+            CachedText {
+                text: read_document_text(p),
+                modified,
+                ..entry
+            }
+        };
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.entries.back()?.text.as_deref()
+    }
+}
+
+/// Reindexes a single file (every entry if it's an archive) and swaps its terms in the dictionary
+/// without rebuilding it, a file that's gone just gets dropped
+pub fn update_document(
+    model: &mut HashMap<String, Document>,
+    dict: &mut TermDictionary,
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<(), IndexError> {
+    let path = p.to_string_lossy().to_string();
+    let entry_prefix = format!("{path}{ARCHIVE_SEPARATOR}");
+    let old: Vec<String> = model
+        .keys()
+        .filter(|k| **k == path || k.starts_with(&entry_prefix))
+        .cloned()
+        .collect();
+    for k in old {
+        if let Some(d) = model.remove(&k) {
+            dict.remove_document(&k, &d);
+        }
+    }
+    if !p.exists() {
+        return Ok(());
+    }
+    for (k, d) in analyze_file(p, opts)? {
+        dict.add_document(&k, &d);
+        model.insert(k, d);
+    }
+    Ok(())
+}
+
+/// Indexes every file under `p`, files and subdirectories that can't be read get logged and
+/// skipped, only `p` itself not being readable is an error
+pub fn analyze_dir(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+) -> Result<HashMap<String, Document>, IndexError> {
+    analyze_dir_incremental(p, opts, &mut HashMap::new())
+}
+
+/// Like `analyze_dir`, but the files that didn't change since they were indexed into `previous`
+/// (same modification time and size) aren't read again, their documents get moved over from it
+/// instead. What's left in `previous` afterwards are the documents of files that are gone (and the
+/// ones from other directories)
+pub fn analyze_dir_incremental(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+    previous: &mut HashMap<String, Document>,
+) -> Result<HashMap<String, Document>, IndexError> {
+    let scan = scan_dir(p, opts, &file_stamps(previous), &|_| {
+        std::ops::ControlFlow::Continue(())
+    })?;
+    Ok(scan.into_model(previous))
+}
+
+/// Indexes the files under `p` that don't match their stamp in `stamps` (see `file_stamps`),
+/// `on_file` gets called once every file is done, whether it was read or not, the scan stops early
+/// (with whatever it found so far) once it returns `ControlFlow::Break`
+pub fn scan_dir(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+    stamps: &HashMap<String, FileStamp>,
+    on_file: &(dyn Fn(&std::path::Path) -> std::ops::ControlFlow<()> + Sync),
+) -> Result<DirScan, IndexError> {
+    let scan = analyze_dir_at_depth(p, opts, stamps, on_file, 0)?;
+    if !scan.unchanged.is_empty() {
+        log::info!(
+            "{} files in {} didn't change since they were indexed",
+            scan.unchanged.len(),
+            p.display()
+        );
+    }
+    Ok(scan)
+}
+
+/// How many files `scan_dir` would go through, to tell how far along it is
+pub fn count_files(p: &std::path::Path, opts: &IndexOptions) -> usize {
+    count_files_at_depth(p, opts, 0)
+}
+
+fn count_files_at_depth(p: &std::path::Path, opts: &IndexOptions, depth: usize) -> usize {
+    let Ok(entries) = p.read_dir() else {
+        return 0;
+    };
+    let mut count = 0;
+    for d in entries.flatten() {
+        let Ok(meta) = d.metadata() else {
+            continue;
+        };
+        if meta.is_file() {
+            count += 1;
+        } else if opts.max_depth.is_none_or(|max| depth < max) {
+            count += count_files_at_depth(&d.path(), opts, depth + 1);
+        }
+    }
+    count
+}
+
+/// Unix seconds a file was modified at and its size in bytes
+pub type FileStamp = (u64, u64);
+
+/// The stamps of the files the documents of `model` were read from when they got indexed, the
+/// entries of an archive all have the stamp of the archive
+pub fn file_stamps(model: &HashMap<String, Document>) -> HashMap<String, FileStamp> {
+    model
+        .iter()
+        .filter(|(_, d)| d.modified != 0)
+        .map(|(k, d)| (document_file(k).to_string(), (d.modified, d.size)))
+        .collect()
+}
+
+fn file_stamp(meta: &std::fs::Metadata) -> FileStamp {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |m| m.as_secs());
+    (modified, meta.len())
+}
+
+// the file a document was read from, the archive for archive entries
+fn document_file(doc: &str) -> &str {
+    doc.split_once(ARCHIVE_SEPARATOR).map_or(doc, |(a, _)| a)
+}
+
+/// What indexing a directory found, the files that still match their stamp only get listed
+#[derive(Default)]
+pub struct DirScan {
+    docs: HashMap<String, Document>,
+    unchanged: HashSet<String>,
+}
+
+impl DirScan {
+    pub fn extend(&mut self, other: DirScan) {
+        self.docs.extend(other.docs);
+        self.unchanged.extend(other.unchanged);
+    }
+
+    /// The documents that got read along with the ones of the unchanged files, which get moved
+    /// over from `previous`
+    pub fn into_model(self, previous: &mut HashMap<String, Document>) -> HashMap<String, Document> {
+        let mut docs = self.docs;
+        if self.unchanged.is_empty() {
+            return docs;
+        }
+        let kept: Vec<String> = previous
+            .keys()
+            .filter(|k| self.unchanged.contains(document_file(k)))
+            .cloned()
+            .collect();
+        for k in kept {
+            if let Some(d) = previous.remove(&k) {
+                docs.insert(k, d);
+            }
+        }
+        docs
+    }
+}
+
+fn analyze_dir_at_depth(
+    p: &std::path::Path,
+    opts: &IndexOptions,
+    stamps: &HashMap<String, FileStamp>,
+    on_file: &(dyn Fn(&std::path::Path) -> std::ops::ControlFlow<()> + Sync),
+    depth: usize,
+) -> Result<DirScan, IndexError> {
+    let mut scan = DirScan::default();
+    let entries = p
+        .read_dir()
+        .map_err(|e| IndexError::new(p, IndexErrorKind::ReadDir(e)))?;
+    // NOTE: Scoped so the subdirectories can share the stamps instead of each getting a copy
+    std::thread::scope(|s| {
+        let mut on_going = vec![];
+        for d in entries {
+            let d = match d {
+                Ok(d) => d,
+                Err(e) => {
+                    log::warn!("Skipping unreadable entry of {}: {e}", p.display());
+                    continue;
+                }
+            };
+            let path = d.path();
+            let meta = match d.metadata() {
+                Ok(m) => m,
+                Err(e) => {
+                    log::warn!(
+                        "Skipping {}",
+                        IndexError::new(&path, IndexErrorKind::Metadata(e))
+                    );
+                    continue;
+                }
+            };
+            if meta.is_file() {
+                let file = path.to_string_lossy();
+                if stamps.get(file.as_ref()) == Some(&file_stamp(&meta)) {
+                    scan.unchanged.insert(file.to_string());
+                    if on_file(&path).is_break() {
+                        break;
+                    }
+                    continue;
+                }
+                // NOTE: A parser choking on one broken file shouldn't take the whole index with it
+                let analyzed = std::panic::catch_unwind(|| analyze_file(&path, opts))
+                    .unwrap_or_else(|_| Err(IndexError::new(&path, IndexErrorKind::Panicked)));
+                match analyzed {
+                    Ok(docs) => scan.docs.extend(docs),
+                    Err(e) if e.is_skipped() => log::info!("Skipping {e}"),
+                    Err(e) => log::warn!("Skipping {e}"),
+                }
+                if on_file(&path).is_break() {
+                    break;
+                }
+            } else if opts.max_depth.is_none_or(|max| depth < max) {
+                let handle =
+                    s.spawn(move || analyze_dir_at_depth(&path, opts, stamps, on_file, depth + 1));
+                on_going.push((d.path(), handle));
+            }
+        }
+        for (dir, handle) in on_going {
+            match handle.join() {
+                Ok(Ok(x)) => scan.extend(x),
+                Ok(Err(e)) => log::warn!("Skipping {e}"),
+                Err(_) => log::error!(
+                    "Skipping {}",
+                    IndexError::new(&dir, IndexErrorKind::Panicked)
+                ),
+            }
+        }
+    });
+    Ok(scan)
+}
+
+/// Finds the most recent modification time of any file under `p`
+pub fn newest_modification(p: &std::path::Path) -> Option<std::time::SystemTime> {
+    let mut newest = None;
+    for d in p.read_dir().ok()?.flatten() {
+        let Ok(meta) = d.metadata() else {
+            continue;
+        };
+        let modified = if meta.is_file() {
+            meta.modified().ok()
+        } else {
+            newest_modification(&d.path())
+        };
+        newest = newest.max(modified);
+    }
+    newest
+}
+
+/// Pdf bookmark titles, they're basically section headings
+pub const FIELD_OUTLINE: &str = "outline";
+/// Email subject lines
+pub const FIELD_SUBJECT: &str = "subject";
+/// Image alt texts and figure captions of xml/xhtml documents
+pub const FIELD_CAPTION: &str = "caption";
+/// Notes on pdf annotations, they mark what the reader found important
+pub const FIELD_ANNOTATION: &str = "annotation";
+/// The directories and the file name of a document, see `IndexOptions::path_terms`
+pub const FIELD_PATH: &str = "path";
+
+// the directory names and the file name without its extension of a document path, archive
+// entries are in a directory named after the archive (e.g. `/docs/taxes/2023/return.pdf` ->
+// "docs taxes 2023 return")
+// NOTE: The directories above the document directories (`home`, the user name...) get indexed
+// too, but they're in (nearly) every document so their idf makes them count for next to nothing
+fn path_words(doc: &str) -> String {
+    let doc = doc.replace(ARCHIVE_SEPARATOR, "/");
+    let p = std::path::Path::new(&doc);
+    let dirs = p.parent().into_iter().flat_map(|d| d.components());
+    dirs.filter_map(|c| match c {
+        std::path::Component::Normal(s) => Some(s),
+        _ => None,
+    })
+    .chain(p.file_stem())
+    .map(|s| s.to_string_lossy())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+// how much more a match in a field counts than one in the body text
+fn field_boost(field: &str) -> f64 {
+    match field {
+        FIELD_OUTLINE => 3.0,
+        FIELD_SUBJECT => 3.0,
+        FIELD_CAPTION => 2.0,
+        FIELD_ANNOTATION => 3.0,
+        // folder names are a hint of what a document is about, not as strong as a title
+        FIELD_PATH => 2.0,
+        // the tagged lines of `IndexOptions::field_tags`, usually titles and tags
+        _ => 3.0,
+    }
+}
+
+/// Term counts of some text that is scored apart from the body (like a pdf outline)
+#[derive(Debug, Default, SchemaRead, SchemaWrite, Serialize, Deserialize)]
+pub struct Field {
+    words: HashMap<String, usize>,
+    raw_words: HashMap<String, usize>,
+    // stemmed term -> ascending word positions, empty unless `IndexOptions::store_positions` is set
+    positions: HashMap<String, Vec<u32>>,
+}
+
+impl Field {
+    fn terms(&self, mode: QueryMode) -> &HashMap<String, usize> {
+        match mode {
+            QueryMode::Stemmed => &self.words,
+            QueryMode::Exact | QueryMode::CaseSensitive => &self.raw_words,
+        }
+    }
+}
+
+#[derive(Debug, SchemaRead, SchemaWrite, Serialize, Deserialize)]
+pub struct Document {
+    words: HashMap<String, usize>,
+    // empty unless `IndexOptions::store_raw_terms` is set
+    raw_words: HashMap<String, usize>,
+    // where the body terms are, empty unless `IndexOptions::store_positions` is set
+    positions: HashMap<String, Vec<u32>>,
+    // boosted named fields, see `field_boost`
+    fields: HashMap<String, Field>,
+    // unix seconds, 0 if unknown
+    modified: u64,
+    // bytes of the file it was read from (the whole archive for the entries of one), 0 if unknown
+    size: u64,
+    // the type of the file (or archive entry) the document was read from
+    file_type: Option<FileType>,
+    // hash of the raw file contents to find duplicates, 0 if unknown
+    content_hash: u64,
+}
+
+impl Document {
+    pub fn file_type(&self) -> Option<FileType> {
+        self.file_type
+    }
+
+    /// Unix seconds, 0 if unknown
+    pub fn modified(&self) -> u64 {
+        self.modified
+    }
+
+    /// How many (stemmed) words got indexed, what the term counts are divided by with
+    /// `TfNormalization::Total`
+    pub fn word_count(&self) -> usize {
+        self.words.values().sum()
+    }
+
+    fn terms(&self, mode: QueryMode) -> &HashMap<String, usize> {
+        match mode {
+            QueryMode::Stemmed => &self.words,
+            QueryMode::Exact | QueryMode::CaseSensitive => &self.raw_words,
+        }
+    }
+
+    // the distinct and the total number of terms in the body, what the term counts get divided by
+    // (see `TfNormalization`)
+    fn lengths(&self, mode: QueryMode) -> (usize, usize) {
+        let terms = self.terms(mode);
+        (terms.len(), terms.values().sum())
+    }
+
+    /// Characters of the terms that got indexed (body and fields, without the whitespace between
+    /// them), the stemmed words are a bit shorter unless `IndexOptions::store_raw_terms` is set
+    pub fn indexed_chars(&self) -> usize {
+        let chars = |words: &HashMap<String, usize>, raw_words: &HashMap<String, usize>| {
+            let terms = if raw_words.is_empty() {
+                words
+            } else {
+                raw_words
+            };
+            terms
+                .iter()
+                .map(|(t, count)| t.chars().count() * count)
+                .sum::<usize>()
+        };
+        chars(&self.words, &self.raw_words)
+            + self
+                .fields
+                .values()
+                .map(|f| chars(&f.words, &f.raw_words))
+                .sum::<usize>()
+    }
+
+    fn add_field(&mut self, name: &str, text: &str, opts: &IndexOptions) {
+        self.insert_field(name, tokenize(text, opts));
+    }
+
+    fn insert_field(&mut self, name: &str, mut field: Field) {
+        // only the positions in the body are used
+        field.positions.clear();
+        if !field.words.is_empty() {
+            self.fields.insert(name.to_string(), field);
+        }
+    }
+
+    // whether the two stemmed terms are at most `distance` words apart somewhere in the body
+    fn terms_within(&self, a: &str, b: &str, distance: u32) -> bool {
+        let (Some(pa), Some(pb)) = (self.positions.get(a), self.positions.get(b)) else {
+            return false;
+        };
+        // both are ascending, so walking them side by side finds the closest pair
+        let (mut i, mut j) = (0, 0);
+        while i < pa.len() && j < pb.len() {
+            if pa[i].abs_diff(pb[j]) <= distance {
+                return true;
+            }
+            if pa[i] < pb[j] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        false
+    }
+
+    // whether the stemmed terms come one right after the other somewhere in the body, never without
+    // `IndexOptions::store_positions` since there's no telling where the words are then
+    fn contains_phrase(&self, phrase: &[String]) -> bool {
+        let Some((first, rest)) = phrase.split_first() else {
+            return true;
+        };
+        let Some(starts) = self.positions.get(first) else {
+            return false;
+        };
+        let Some(rest) = rest
+            .iter()
+            .map(|t| self.positions.get(t))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        starts.iter().any(|start| {
+            rest.iter()
+                .zip(1..)
+                .all(|(positions, i)| positions.binary_search(&(start + i)).is_ok())
+        })
+    }
+
+    // occurrences of the term in the body plus the boosted ones in the fields
+    fn weighted_count(&self, term: &str, mode: QueryMode) -> f64 {
+        let body = self.terms(mode).get(term).copied().unwrap_or(0) as f64;
+        let fields: f64 = self
+            .fields
+            .iter()
+            .filter_map(|(name, f)| {
+                let count = *f.terms(mode).get(term)?;
+                Some(count as f64 * field_boost(name))
+            })
+            .sum();
+        body + fields
+    }
+
+    // boosted occurrences of the term in just the one field
+    fn field_count(&self, field: &str, term: &str, mode: QueryMode) -> f64 {
+        self.fields
+            .get(field)
+            .and_then(|f| f.terms(mode).get(term))
+            .map_or(0.0, |count| *count as f64 * field_boost(field))
+    }
+
+    // every term of the body and the fields
+    fn all_terms(&self, mode: QueryMode) -> impl Iterator<Item = &String> {
+        self.terms(mode)
+            .keys()
+            .chain(self.fields.values().flat_map(move |f| f.terms(mode).keys()))
+    }
+
+    // the count of every term of the body and the fields, a term in several of them comes up once
+    // for each
+    fn all_term_counts(&self, mode: QueryMode) -> impl Iterator<Item = (&String, usize)> {
+        self.terms(mode)
+            .iter()
+            .chain(self.fields.values().flat_map(move |f| f.terms(mode)))
+            .map(|(t, count)| (t, *count))
+    }
+
+    fn unique_terms(&self, mode: QueryMode) -> HashSet<&String> {
+        self.all_terms(mode).collect()
+    }
+
+    fn add_date_terms(&mut self) {
+        let (year, month, _) = civil_date(self.modified);
+        *self.words.entry(year.to_string()).or_insert(0) += 1;
+        *self.words.entry(format!("{year}-{month:02}")).or_insert(0) += 1;
+    }
+}
+
+// a NEAR match multiplies the score of the document by this
+const NEAR_BOOST: f64 = 2.0;
+
+/// The distance of a `NEAR/<n>` operator, `near:<n>` works too since `/` can't be typed into the
+/// query box
+pub fn parse_near_operator(t: &str) -> Option<u32> {
+    let t = t.to_ascii_lowercase();
+    t.strip_prefix("near/")
+        .or_else(|| t.strip_prefix("near:"))?
+        .parse()
+        .ok()
+}
+
+/// Pulls the double quoted phrases (e.g. `"machine learning"`) out of the query terms, their words
+/// stay in the terms without the quotes. A quote that never gets closed runs to the end of the query
+pub fn parse_phrases<'a>(terms: &[&'a str]) -> (Vec<&'a str>, Vec<Vec<&'a str>>) {
+    let mut words = vec![];
+    let mut phrases = vec![];
+    let mut current: Option<Vec<&str>> = None;
+    for t in terms {
+        // a quote on its own (cjk bigrams get split off of it) opens or closes one
+        if *t == "\"" {
+            match current.take() {
+                Some(phrase) => phrases.push(phrase),
+                None => current = Some(vec![]),
+            }
+            continue;
+        }
+        let (opens, t) = t.strip_prefix('"').map_or((false, *t), |t| (true, t));
+        let (closes, t) = t.strip_suffix('"').map_or((false, t), |t| (true, t));
+        if opens && current.is_none() {
+            current = Some(vec![]);
+        }
+        if !t.is_empty() {
+            words.push(t);
+            if let Some(phrase) = &mut current {
+                phrase.push(t);
+            }
+        }
+        if closes && let Some(phrase) = current.take() {
+            phrases.push(phrase);
+        }
+    }
+    phrases.extend(current);
+    // a single quoted word is just a term
+    phrases.retain(|p| p.len() > 1);
+    (words, phrases)
+}
+
+/// The term after the `-` of an excluded query term (e.g. `-tokio`), a `-` on its own or one that
+/// isn't followed by a letter or digit (like in `--`) doesn't exclude anything
+pub fn parse_excluded_term(t: &str) -> Option<&str> {
+    t.strip_prefix('-')
+        .filter(|t| t.starts_with(|c: char| c.is_alphanumeric() || c == '='))
+}
+
+/// Splits the field off of a `field:value` query term (e.g. `title:rust`), which only matches the
+/// value in that field of the documents
+pub fn parse_field_term(t: &str) -> (Option<&str>, &str) {
+    match t.split_once(':') {
+        Some((field, value))
+            if !value.is_empty()
+                && !field.is_empty()
+                && field
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+        {
+            (Some(field), value)
+        }
+        _ => (None, t),
+    }
+}
+
+/// Splits the `=` prefix off of a query term (e.g. `=Running`), those terms are matched unstemmed
+/// like with `QueryMode::Exact` while the rest of the query stays stemmed
+pub fn parse_exact_term(t: &str) -> (bool, &str) {
+    match t.strip_prefix('=') {
+        Some(term) if !term.is_empty() => (true, term),
+        _ => (false, t),
+    }
+}
+
+/// Splits the `^<weight>` suffix off of a query term (e.g. `rust^3`), terms without one weigh 1
+pub fn parse_weighted_term(t: &str) -> (&str, f64) {
+    if let Some((term, weight)) = t.rsplit_once('^')
+        && let Ok(weight) = weight.parse::<f64>()
+        && weight.is_finite()
+        && weight >= 0.0
+    {
+        return (term, weight);
+    }
+    (t, 1.0)
+}
+
+/// Which documents each term is in and how often (in the body and the fields together, without
+/// the field boosts), so a query only has to look at the documents that have one of its terms
+#[derive(Debug, Default)]
+pub struct InvertedIndex {
+    // NOTE: The paths are shared between all the postings of a document instead of being copied
+    // into every one of them
+    postings: HashMap<String, HashMap<std::sync::Arc<str>, usize>>,
+}
+
+impl InvertedIndex {
+    /// The documents `term` is in along with how often it's in them
+    pub fn postings(&self, term: &str) -> impl Iterator<Item = (&str, usize)> {
+        self.postings
+            .get(term)
+            .into_iter()
+            .flatten()
+            .map(|(p, count)| (p.as_ref(), *count))
+    }
+}
+
+// the sorted terms of one of the term maps, along with the documents each one is in so a
+// document can be taken out again without rebuilding the whole list
+#[derive(Debug, Default)]
+struct TermList {
+    sorted: Vec<String>,
+    index: InvertedIndex,
+    // `Document::lengths` of every document, so a query doesn't have to add them up every time
+    lengths: HashMap<std::sync::Arc<str>, (usize, usize)>,
+}
+
+impl TermList {
+    fn build<'a>(docs: impl Iterator<Item = (&'a String, &'a Document)>, mode: QueryMode) -> Self {
+        let mut list = Self::default();
+        for (path, d) in docs {
+            list.add_postings(path, d, mode);
+        }
+        list.sorted = list.index.postings.keys().cloned().collect();
+        list.sorted.sort_unstable();
+        list
+    }
+
+    // the terms that weren't in any document before
+    fn add_postings<'a>(&mut self, path: &str, doc: &'a Document, mode: QueryMode) -> Vec<&'a str> {
+        let path: std::sync::Arc<str> = path.into();
+        self.lengths.insert(path.clone(), doc.lengths(mode));
+        let mut new_terms = vec![];
+        for (t, count) in doc.all_term_counts(mode) {
+            let posting = self.index.postings.entry(t.clone()).or_default();
+            if posting.is_empty() {
+                new_terms.push(t.as_str());
+            }
+            *posting.entry(path.clone()).or_insert(0) += count;
+        }
+        new_terms
+    }
+
+    fn add(&mut self, path: &str, doc: &Document, mode: QueryMode) {
+        for t in self.add_postings(path, doc, mode) {
+            let i = self.sorted.partition_point(|s| s.as_str() < t);
+            self.sorted.insert(i, t.to_string());
+        }
+    }
+
+    fn remove(&mut self, path: &str, doc: &Document, mode: QueryMode) {
+        self.lengths.remove(path);
+        for t in doc.unique_terms(mode) {
+            let Some(posting) = self.index.postings.get_mut(t) else {
+                continue;
+            };
+            posting.remove(path);
+            if posting.is_empty() {
+                self.index.postings.remove(t);
+                if let Ok(i) = self.sorted.binary_search(t) {
+                    self.sorted.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// Every distinct term in the model, sorted so prefix lookups are just a binary search
+#[derive(Debug, Default)]
+pub struct TermDictionary {
+    terms: TermList,
+    raw_terms: TermList,
+    documents: usize,
+    // the `Document::word_count` of all of them together, for the average length BM25 needs
+    words: usize,
+}
+
+impl TermDictionary {
+    pub fn build(model: &HashMap<String, Document>) -> Self {
+        Self {
+            terms: TermList::build(model.iter(), QueryMode::Stemmed),
+            raw_terms: TermList::build(model.iter(), QueryMode::Exact),
+            documents: model.len(),
+            words: model.values().map(Document::word_count).sum(),
+        }
+    }
+
+    fn add_document(&mut self, path: &str, doc: &Document) {
+        self.terms.add(path, doc, QueryMode::Stemmed);
+        self.raw_terms.add(path, doc, QueryMode::Exact);
+        self.documents += 1;
+        self.words += doc.word_count();
+    }
+
+    fn remove_document(&mut self, path: &str, doc: &Document) {
+        self.terms.remove(path, doc, QueryMode::Stemmed);
+        self.raw_terms.remove(path, doc, QueryMode::Exact);
+        self.documents = self.documents.saturating_sub(1);
+        self.words = self.words.saturating_sub(doc.word_count());
+    }
+
+    // words per document
+    fn average_length(&self) -> f64 {
+        (self.words as f64 / self.documents.max(1) as f64).max(1.0)
+    }
+
+    fn list(&self, mode: QueryMode) -> &TermList {
+        match mode {
+            QueryMode::Stemmed => &self.terms,
+            QueryMode::Exact | QueryMode::CaseSensitive => &self.raw_terms,
+        }
+    }
+
+    fn sorted(&self, mode: QueryMode) -> &[String] {
+        &self.list(mode).sorted
+    }
+
+    /// The documents of the term map of `mode` by term
+    pub fn index(&self, mode: QueryMode) -> &InvertedIndex {
+        &self.list(mode).index
+    }
+
+    pub fn terms_with_prefix(&self, prefix: &str, mode: QueryMode) -> Vec<&str> {
+        let terms = self.sorted(mode);
+        let start = terms.partition_point(|t| t.as_str() < prefix);
+        terms[start..]
+            .iter()
+            .take_while(|t| t.starts_with(prefix))
+            .map(|t| t.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, term: &str, mode: QueryMode) -> bool {
+        self.sorted(mode)
+            .binary_search_by(|t| t.as_str().cmp(term))
+            .is_ok()
+    }
+
+    /// Finds the indexed term with the smallest edit distance to `term` (if it's close enough)
+    pub fn closest(&self, term: &str, mode: QueryMode) -> Option<&str> {
+        let len = term.chars().count();
+        self.sorted(mode)
+            .iter()
+            .filter(|t| t.chars().count().abs_diff(len) <= MAX_SUGGESTION_DISTANCE)
+            .map(|t| (edit_distance(t, term), t))
+            .filter(|(d, _)| *d <= MAX_SUGGESTION_DISTANCE)
+            .min_by_key(|(d, _)| *d)
+            .map(|(_, t)| t.as_str())
+    }
+}
+
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+// levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Rewrites the query with every unknown term swapped for the closest indexed one, `None` if
+/// there was nothing to correct
+pub fn suggest_correction(
+    dict: &TermDictionary,
+    terms: &[&str],
+    mode: QueryMode,
+) -> Option<String> {
+    let en_stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
+    let mut corrected_any = false;
+    let corrected: Vec<String> = terms
+        .iter()
+        .map(|t| {
+            // the quotes of a phrase stay around the corrected word
+            let (open, word) = t.strip_prefix('"').map_or(("", *t), |w| ("\"", w));
+            let (word, close) = word.strip_suffix('"').map_or((word, ""), |w| (w, "\""));
+            let (term, _) = parse_weighted_term(word);
+            let normalized = mode.normalize(&en_stemmer, term);
+            // exact terms are meant literally (like code identifiers), so they're never corrected
+            if term.is_empty()
+                || parse_excluded_term(term).is_some()
+                || term.ends_with('*')
+                || parse_exact_term(parse_field_term(term).1).0
+                || Filter::parse(term).is_some()
+                || dict.contains(&normalized, mode)
+            {
+                return t.to_string();
+            }
+            match dict.closest(&normalized, mode) {
+                Some(c) => {
+                    corrected_any = true;
+                    // keeps the weight suffix
+                    format!("{open}{c}{}{close}", &word[term.len()..])
+                }
+                None => t.to_string(),
+            }
+        })
+        .collect();
+    corrected_any.then(|| corrected.join(" "))
+}
+
+/// One document found by `query`
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub path: String,
+    pub score: f64,
+    /// the (normalized) query terms the document contains, `field:term` for field terms
+    pub matched_terms: Vec<String>,
+    /// `query` leaves it `None` since the model doesn't keep the text, see `snippet` for cutting
+    /// one out of the text of the document
+    pub snippet: Option<String>,
+}
+
+/// `query` with just the paths and scores of the results
+pub fn do_query(
+    model: &HashMap<String, Document>,
+    dict: &TermDictionary,
+    terms: &[&str],
+    mode: QueryMode,
+    budget: Option<std::time::Duration>,
+    scoring: Scoring,
+    score_scale: ScoreScale,
+) -> (Vec<(String, f64)>, bool) {
+    let (results, partial) = query(model, dict, terms, mode, budget, scoring, score_scale);
+    (
+        results.into_iter().map(|r| (r.path, r.score)).collect(),
+        partial,
+    )
+}
+
+/// Ranks the documents matching `terms`, once `budget` runs out the documents scored so far are
+/// returned and the second value is set to mark the results as partial.
+/// `after:`/`before:` and `size:` terms only keep the documents modified in that range or of that
+/// size, on their own they list every document they keep, newest first (with a score of 0).
+/// `"quoted phrases"` only keep the documents with their words right after each other in the body
+/// and `-excluded` terms drop the documents that have them, however well they'd score
+// NOTE: Only the candidates out of the inverted index get walked (once, split over the threads),
+// the document frequencies for the idf come from its posting lists and the lengths of the
+// documents were worked out when they got added to the dictionary. So the filters, phrases,
+// excluded terms and the budget only take documents out of the results, the scores of the rest
+// stay the same
+pub fn query(
+    model: &HashMap<String, Document>,
+    dict: &TermDictionary,
+    terms: &[&str],
+    mode: QueryMode,
+    budget: Option<std::time::Duration>,
+    scoring: Scoring,
+    score_scale: ScoreScale,
+) -> (Vec<SearchResult>, bool) {
+    let start = std::time::Instant::now();
+    let en_stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
+    // the positions are keyed by the lowercase stems, whatever the query mode is
+    let position_key = |t: &str| {
+        let (t, _) = parse_weighted_term(t);
+        let (_, t) = parse_exact_term(parse_field_term(t).1);
+        en_stemmer.stem(&t.to_lowercase()).to_string()
+    };
+    let (terms, phrases) = parse_phrases(terms);
+    let phrases: Vec<Vec<String>> = phrases
+        .iter()
+        .map(|p| p.iter().map(|t| position_key(t)).collect())
+        .collect();
+    let mut filters = vec![];
+    let mut excluded = vec![];
+    let terms: Vec<&str> = terms
+        .iter()
+        .filter(|t| {
+            if let Some(f) = Filter::parse(t) {
+                filters.push(f);
+                return false;
+            }
+            let Some(t) = parse_excluded_term(t) else {
+                return true;
+            };
+            let (field, t) = parse_field_term(t);
+            let (exact, t) = parse_exact_term(t);
+            let mode = if exact { mode.unstemmed() } else { mode };
+            excluded.push((mode.normalize(&en_stemmer, t), field, mode));
+            false
+        })
+        .copied()
+        .collect();
+    // `a NEAR/5 b` pairs up the terms around the operator, they still count as regular terms but
+    // documents with them at most 5 words apart rank higher
+    let mut near_pairs = vec![];
+    let mut plain_terms: Vec<&str> = vec![];
+    for (i, t) in terms.iter().enumerate() {
+        let Some(distance) = parse_near_operator(t) else {
+            plain_terms.push(t);
+            continue;
+        };
+        if let (Some(a), Some(b)) = (plain_terms.last(), terms.get(i + 1)) {
+            near_pairs.push((position_key(a), position_key(b), distance));
+        }
+    }
+    let terms = plain_terms;
+    // and without any of the excluded terms. It only decides which documents are results, the idf
+    // still counts the ones it leaves out (see `doc_freqs` below)
+    let in_range = |d: &Document| {
+        filters.iter().all(|f| f.matches(d))
+            && !excluded.iter().any(|(t, field, mode)| match field {
+                Some(f) => d.field_count(f, t, *mode) > 0.0,
+                None => d.weighted_count(t, *mode) > 0.0,
+            })
+    };
+    if terms.is_empty() {
+        if filters.is_empty() {
+            return (vec![], false);
+        }
+        let mut docs: Vec<_> = model.iter().filter(|(_, d)| in_range(d)).collect();
+        docs.sort_by_key(|(_, d)| std::cmp::Reverse(d.modified));
+        return (
+            docs.into_iter()
+                .map(|(p, _)| SearchResult {
+                    path: p.clone(),
+                    score: 0.0,
+                    matched_terms: vec![],
+                    snippet: None,
+                })
+                .collect(),
+            false,
+        );
+    }
+    // before the prefixes get expanded, a document can only match one of the expansions anyway
+    let total_weight: f64 = terms.iter().map(|t| parse_weighted_term(t).1).sum();
+    let terms: Vec<_> = terms
+        .iter()
+        .flat_map(|t| {
+            let (t, weight) = parse_weighted_term(t);
+            let (field, t) = parse_field_term(t);
+            let (exact, t) = parse_exact_term(t);
+            let mode = if exact { mode.unstemmed() } else { mode };
+            // terms ending with `*` get expanded into every indexed term they are a prefix of
+            match t.strip_suffix('*') {
+                Some(prefix) => dict
+                    .terms_with_prefix(&mode.fold_case(prefix), mode)
+                    .into_iter()
+                    .map(|t| (t.to_string(), weight, field, mode))
+                    .collect(),
+                None => vec![(mode.normalize(&en_stemmer, t), weight, field, mode)],
+            }
+        })
+        .collect();
+    // only the documents with at least one of the terms can match, the rest never gets looked at
+    let mut candidates: HashSet<&str> = HashSet::new();
+    for (t, _, _, mode) in &terms {
+        candidates.extend(dict.index(*mode).postings(t).map(|(p, _)| p));
+    }
+    // how many documents each term is in for the idf, the postings don't tell the fields apart so
+    // the documents of a field term have to be checked
+    let doc_freqs: Vec<usize> = terms
+        .iter()
+        .map(|(t, _, field, mode)| {
+            let postings = dict.index(*mode).postings(t);
+            match field {
+                Some(f) => postings
+                    .filter(|(p, _)| {
+                        model
+                            .get(*p)
+                            .is_some_and(|d| d.field_count(f, t, *mode) > 0.0)
+                    })
+                    .count(),
+                None => postings.count(),
+            }
+        })
+        .collect();
+    // every thread goes through its own chunk of the candidates once, collecting the documents
+    // that match
+    let entries: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|p| model.get_key_value(p))
+        .collect();
+    if !phrases.is_empty()
+        && entries
+            .iter()
+            .any(|(_, d)| d.positions.is_empty() && !d.words.is_empty())
+    {
+        log::warn!(
+            "Some documents were indexed without their word positions, they can't match a phrase \
+             until they're reindexed with `index_positions` on"
+        );
+    }
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = entries.len().div_ceil(threads).max(1);
+    let partial = std::sync::atomic::AtomicBool::new(false);
+    let chunks: Vec<ChunkMatches> = std::thread::scope(|s| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let (terms, partial, in_range, near_pairs, phrases) =
+                    (&terms, &partial, &in_range, &near_pairs, &phrases);
+                s.spawn(move || {
+                    let mut matches = ChunkMatches { docs: vec![] };
+                    for (path, data) in chunk {
+                        if let Some(budget) = budget
+                            && start.elapsed() > budget
+                        {
+                            partial.store(true, std::sync::atomic::Ordering::Relaxed);
+                            break;
+                        }
+                        if !in_range(data) || !phrases.iter().all(|p| data.contains_phrase(p)) {
+                            continue;
+                        }
+                        let counts: Vec<f64> = terms
+                            .iter()
+                            .map(|(t, _, field, mode)| match field {
+                                Some(f) => data.field_count(f, t, *mode),
+                                None => data.weighted_count(t, *mode),
+                            })
+                            .collect();
+                        if counts.iter().all(|c| *c == 0.0) {
+                            continue;
+                        }
+                        // every candidate is in the dictionary, unless it's out of sync with the model
+                        let (unique, words) = dict
+                            .list(mode)
+                            .lengths
+                            .get(path.as_str())
+                            .copied()
+                            .unwrap_or_else(|| data.lengths(mode));
+                        let total = match scoring {
+                            Scoring::TfIdf(TfNormalization::Unique) => unique,
+                            Scoring::TfIdf(TfNormalization::Total) | Scoring::Bm25 { .. } => words,
+                        }
+                        .max(1);
+                        let near = near_pairs
+                            .iter()
+                            .filter(|(a, b, distance)| data.terms_within(a, b, *distance))
+                            .count();
+                        matches.docs.push((*path, counts, total, near));
+                    }
+                    matches
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    // every document counts for the idf, the ones without any of the terms too
+    let documents = model.len();
+    let average_length = dict.average_length();
+    let mut docs = vec![];
+    for (path, counts, total, near) in chunks.iter().flat_map(|c| &c.docs) {
+        let mut point = 0.0;
+        for (((_, weight, _, _), count), freq) in terms.iter().zip(counts).zip(&doc_freqs) {
+            if *count == 0.0 {
+                continue;
+            }
+            // only 0 when the dictionary is out of sync with the model
+            let freq = (*freq).max(1);
+            point += weight
+                * match scoring {
+                    Scoring::TfIdf(_) => {
+                        let tf = count / *total as f64;
+                        tf * (documents as f64 / freq as f64).log2()
+                    }
+                    Scoring::Bm25 { k1, b } => {
+                        let length = 1.0 - b + b * *total as f64 / average_length;
+                        let tf = count * (k1 + 1.0) / (count + k1 * length);
+                        tf * bm25_idf(documents, freq)
+                    }
+                };
+        }
+        point *= NEAR_BOOST.powi(*near as i32);
+        docs.push((*path, point, counts));
+    }
+    let best = match scoring {
+        // a document made of nothing but the query terms that no other document has (tf 1, idf
+        // of log2(documents) for each), field boosts can push a tf past 1 so the percentage gets
+        // capped
+        Scoring::TfIdf(_) => (documents as f64).log2() * total_weight,
+        // the same for BM25, where the tf can get close to k1 + 1 at most
+        Scoring::Bm25 { k1, .. } => bm25_idf(documents, 1) * (k1 + 1.0) * total_weight,
+    };
+    docs.sort_by(|(_, b1, _), (_, a1, _)| a1.total_cmp(b1));
+    let docs = docs
+        .iter()
+        .filter(|(_p, d, _)| *d != 0.0)
+        .map(|(p, d, counts)| {
+            let score = match score_scale {
+                ScoreScale::Raw => *d,
+                ScoreScale::Percent if best > 0.0 => (d / best * 100.0).min(100.0),
+                ScoreScale::Percent => 0.0,
+            };
+            let mut matched_terms: Vec<String> = vec![];
+            for ((t, _, field, _), count) in terms.iter().zip(*counts) {
+                let t = match field {
+                    Some(f) => format!("{f}:{t}"),
+                    None => t.clone(),
+                };
+                if *count > 0.0 && !matched_terms.contains(&t) {
+                    matched_terms.push(t);
+                }
+            }
+            SearchResult {
+                path: (*p).clone(),
+                score,
+                matched_terms,
+                snippet: None,
+            }
+        })
+        .collect();
+    (docs, partial.into_inner())
+}
+
+// what one of the `query` threads found in its part of the candidates
+struct ChunkMatches<'a> {
+    // path, weighted count of every query term, the number of terms in the document (see
+    // `TfNormalization`) and how many of the NEAR pairs are close enough in it
+    docs: Vec<(&'a String, Vec<f64>, usize, usize)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn stem(word: &str) -> String {
+        let stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
+        QueryMode::Stemmed.normalize(&stemmer, word)
+    }
+
+    // the (stemmed) count of `word` in the body
+    fn count(d: &Document, word: &str) -> usize {
+        d.words.get(&stem(word)).copied().unwrap_or(0)
+    }
+
+    fn document(text: &str) -> Document {
+        create_document_from_text(text, &IndexOptions::default())
+    }
+
+    fn model(docs: &[(&str, &str)]) -> HashMap<String, Document> {
+        docs.iter()
+            .map(|(p, text)| (p.to_string(), document(text)))
+            .collect()
+    }
+
+    fn query(model: &HashMap<String, Document>, terms: &[&str]) -> Vec<String> {
+        let dict = TermDictionary::build(model);
+        let (docs, partial) = do_query(
+            model,
+            &dict,
+            terms,
+            QueryMode::Stemmed,
+            None,
+            Scoring::default(),
+            ScoreScale::default(),
+        );
+        assert!(!partial);
+        docs.into_iter().map(|(p, _)| p).collect()
+    }
+
+    #[test]
+    fn parses_phrases() {
+        let (words, phrases) = parse_phrases(&["\"machine", "learning\"", "rust", "\"solo\""]);
+        assert_eq!(words, ["machine", "learning", "rust", "solo"]);
+        assert_eq!(phrases, [vec!["machine", "learning"]]);
+        // runs to the end when it's never closed
+        let (_, phrases) = parse_phrases(&["a", "\"b", "c"]);
+        assert_eq!(phrases, [vec!["b", "c"]]);
+        let (words, phrases) = parse_phrases(&["\"", "b", "c", "\""]);
+        assert_eq!(words, ["b", "c"]);
+        assert_eq!(phrases, [vec!["b", "c"]]);
+    }
+
+    #[test]
+    fn phrase_queries() {
+        let opts = IndexOptions {
+            store_positions: true,
+            ..Default::default()
+        };
+        let texts = [
+            ("a", "we love machine learning a lot"),
+            ("b", "learning about a machine"),
+            ("c", "the machine, learning"),
+            ("d", "deep thoughts"),
+        ];
+        let with_positions: HashMap<String, Document> = texts
+            .iter()
+            .map(|(p, text)| (p.to_string(), create_document_from_text(text, &opts)))
+            .collect();
+        assert_eq!(query(&with_positions, &["\"machine", "learning\""]), ["a"]);
+        // stemmed like the rest
+        assert_eq!(query(&with_positions, &["\"machines", "learned\""]), ["a"]);
+        assert_eq!(query(&with_positions, &["machine", "learning"]).len(), 3);
+        // without the positions there's no telling if they're next to each other
+        assert!(query(&model(&texts), &["\"machine", "learning\""]).is_empty());
+    }
+
+    #[test]
+    fn excluded_terms() {
+        let m = model(&[
+            ("a", "rust with tokio"),
+            ("b", "rust with async-std"),
+            ("c", "plain rust"),
+            ("d", "python"),
+        ]);
+        let sorted = |terms: &[&str]| {
+            let mut found = query(&m, terms);
+            found.sort();
+            found
+        };
+        assert_eq!(sorted(&["rust", "-tokio"]), ["b", "c"]);
+        // stemmed like the rest
+        assert_eq!(sorted(&["rust", "-tokios"]), ["b", "c"]);
+        // hyphens inside a word and ones on their own are no exclusions
+        assert_eq!(sorted(&["async-std"]), ["b"]);
+        assert_eq!(sorted(&["rust", "-"]), ["a", "b", "c"]);
+        assert_eq!(parse_excluded_term("--"), None);
+        assert_eq!(parse_excluded_term("-=Tokio"), Some("=Tokio"));
+    }
+
+    #[test]
+    fn tokenizes_lowercased_stems() {
+        let d = document("Running runs RUN, the runner ran.");
+        assert_eq!(count(&d, "run"), 3);
+        assert_eq!(count(&d, "runner"), 1);
+        assert_eq!(d.words.get("Running"), None);
+        // punctuation is a term of its own
+        assert_eq!(d.words.get(","), Some(&1));
+    }
+
+    #[test]
+    fn keeps_hyphens_and_apostrophes_inside_words() {
+        let d = document("state-of-the-art don't");
+        assert_eq!(count(&d, "state-of-the-art"), 1);
+        assert_eq!(count(&d, "state"), 0);
+        assert_eq!(count(&d, "don't"), 1);
+        assert_eq!(count(&d, "don"), 0);
+    }
+
+    #[test]
+    fn drops_words_over_the_max_token_length() {
+        let opts = IndexOptions {
+            max_token_length: Some(8),
+            ..Default::default()
+        };
+        let d = create_document_from_text("short aaaaaaaaaaaaaaaa tail", &opts);
+        assert_eq!(count(&d, "short"), 1);
+        assert_eq!(count(&d, "tail"), 1);
+        assert_eq!(d.word_count(), 2);
+    }
+
+    #[test]
+    fn query_on_empty_model() {
+        assert!(query(&HashMap::new(), &["anything"]).is_empty());
+    }
+
+    #[test]
+    fn query_without_terms() {
+        let m = model(&[("a", "some text")]);
+        assert!(query(&m, &[]).is_empty());
+    }
+
+    #[test]
+    fn query_single_term() {
+        let m = model(&[
+            ("cats", "cats purr and cats sleep"),
+            ("dogs", "dogs bark"),
+            ("birds", "birds sing"),
+        ]);
+        assert_eq!(query(&m, &["cat"]), ["cats"]);
+        assert!(query(&m, &["fish"]).is_empty());
+    }
+
+    #[test]
+    fn query_ranks_by_term_frequency() {
+        let m = model(&[
+            ("once", "rust is a language among many other languages"),
+            ("twice", "rust rust everywhere"),
+            ("none", "python is a language"),
+        ]);
+        assert_eq!(query(&m, &["rust"]), ["twice", "once"]);
+    }
+
+    #[test]
+    fn query_ranks_rare_terms_higher() {
+        let m = model(&[
+            ("common", "apple banana"),
+            ("rare", "apple cherry"),
+            ("other", "banana grape"),
+        ]);
+        // cherry is in fewer documents than banana, so it's worth more
+        assert_eq!(query(&m, &["banana", "cherry"])[0], "rare");
+    }
+
+    #[test]
+    fn query_exact_terms() {
+        let opts = IndexOptions {
+            store_raw_terms: true,
+            ..Default::default()
+        };
+        let m: HashMap<String, Document> = [
+            ("running", "running fast"),
+            ("run", "run slow"),
+            ("walk", "walk slow"),
+        ]
+        .into_iter()
+        .map(|(p, text)| (p.to_string(), create_document_from_text(text, &opts)))
+        .collect();
+        let mut stemmed = query(&m, &["running"]);
+        stemmed.sort();
+        assert_eq!(stemmed, ["run", "running"]);
+        assert_eq!(query(&m, &["=running"]), ["running"]);
+        // only the `=` term is unstemmed
+        assert_eq!(query(&m, &["=running", "slows"]).len(), 3);
+        assert!(query(&m, &["=runs"]).is_empty());
+    }
+
+    #[test]
+    fn snippet_around_the_first_match() {
+        let text = "one two three four five (Running) six seven eight nine ten";
+        let s = snippet(text, &["run"], QueryMode::Stemmed, 4);
+        assert_eq!(s.as_deref(), Some("... four five (Running) six seven ..."));
+        // nothing to cut off at the start
+        let s = snippet(text, &["one"], QueryMode::Stemmed, 4);
+        assert_eq!(s.as_deref(), Some("one two three four five ..."));
+        let s = snippet(text, &["ten"], QueryMode::Stemmed, 4);
+        assert_eq!(s.as_deref(), Some("... eight nine ten"));
+        assert_eq!(snippet(text, &["missing"], QueryMode::Stemmed, 4), None);
+    }
+
+    #[test]
+    fn tf_idf_scores() {
+        let m = model(&[
+            ("a", "apple apple pear plum"),
+            ("b", "apple fig fig"),
+            ("c", "kiwi"),
+        ]);
+        let dict = TermDictionary::build(&m);
+        let score = |terms: &[&str]| {
+            let (docs, _) = do_query(
+                &m,
+                &dict,
+                terms,
+                QueryMode::Stemmed,
+                None,
+                Scoring::TfIdf(TfNormalization::Total),
+                ScoreScale::Raw,
+            );
+            docs
+        };
+        // "apple" is in 2 of the 3 documents
+        let idf = (3.0f64 / 2.0).log2();
+        let docs = score(&["apple"]);
+        assert_eq!(docs[0].0, "a");
+        assert!((docs[0].1 - 2.0 / 4.0 * idf).abs() < 1e-9);
+        assert!((docs[1].1 - 1.0 / 3.0 * idf).abs() < 1e-9);
+        // the idf of every term counts the documents it's in
+        let docs = score(&["apple", "fig"]);
+        assert_eq!(docs[0].0, "b");
+        assert!((docs[0].1 - (idf / 3.0 + 2.0 / 3.0 * 3.0f64.log2())).abs() < 1e-9);
+    }
+
+    // raw tf-idf scores by path
+    fn scores(m: &HashMap<String, Document>, terms: &[&str]) -> HashMap<String, f64> {
+        let dict = TermDictionary::build(m);
+        let (docs, _) = do_query(
+            m,
+            &dict,
+            terms,
+            QueryMode::Stemmed,
+            None,
+            Scoring::default(),
+            ScoreScale::Raw,
+        );
+        docs.into_iter().collect()
+    }
+
+    #[test]
+    fn excluded_terms_keep_the_scores() {
+        let m = model(&[
+            ("a", "apple pear"),
+            ("b", "apple apple kiwi"),
+            ("c", "apple tokio"),
+            ("d", "fig"),
+        ]);
+        let all = scores(&m, &["apple"]);
+        let excluded = scores(&m, &["apple", "-tokio"]);
+        assert_eq!(excluded.len(), 2);
+        for (p, score) in excluded {
+            assert!((all[&p] - score).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn filters_keep_the_scores() {
+        let mut m = model(&[
+            ("old", "apple pear"),
+            ("new", "apple apple kiwi"),
+            ("newer", "apple fig"),
+            ("other", "plum"),
+        ]);
+        m.get_mut("old").unwrap().modified = 946684800; // 2000-01-01
+        m.get_mut("new").unwrap().modified = 1609459200; // 2021-01-01
+        m.get_mut("newer").unwrap().modified = 1640995200; // 2022-01-01
+        let all = scores(&m, &["apple"]);
+        let filtered = scores(&m, &["apple", "after:2020-01-01"]);
+        assert_eq!(filtered.len(), 2);
+        for (p, score) in filtered {
+            assert!((all[&p] - score).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn bm25_scores() {
+        let scores = |docs: &[(&str, &str)], scoring| {
+            let m = model(docs);
+            let dict = TermDictionary::build(&m);
+            let (docs, _) = do_query(
+                &m,
+                &dict,
+                &["apple"],
+                QueryMode::Stemmed,
+                None,
+                scoring,
+                ScoreScale::Raw,
+            );
+            docs.into_iter().collect::<HashMap<_, _>>()
+        };
+        let docs = [
+            ("once", "apple x x x x x x x x x"),
+            (
+                "often",
+                "apple apple apple apple apple apple apple apple apple apple",
+            ),
+            ("none", "kiwi"),
+        ];
+        let bm25 = Scoring::Bm25 {
+            k1: BM25_K1,
+            b: BM25_B,
+        };
+        // ten times the occurrences count ten times as much with tf-idf, but not with bm25
+        let s = scores(&docs, Scoring::default());
+        assert!((s["often"] / s["once"] - 10.0).abs() < 1e-9);
+        let s = scores(&docs, bm25);
+        assert!(s["often"] > s["once"]);
+        assert!(s["often"] / s["once"] < 3.0);
+        // the longer document gets pushed down, unless b is 0
+        let docs = [
+            ("short", "apple"),
+            ("long", "apple x x x x x"),
+            ("none", "kiwi"),
+        ];
+        let s = scores(&docs, bm25);
+        assert!(s["short"] > s["long"]);
+        let s = scores(
+            &docs,
+            Scoring::Bm25 {
+                k1: BM25_K1,
+                b: 0.0,
+            },
+        );
+        assert!((s["short"] - s["long"]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverted_index_postings() {
+        let m = model(&[("a", "apple apple pear"), ("b", "apple"), ("c", "kiwi")]);
+        let mut dict = TermDictionary::build(&m);
+        let postings = |dict: &TermDictionary, term: &str| {
+            let mut p: Vec<_> = dict
+                .index(QueryMode::Stemmed)
+                .postings(&stem(term))
+                .map(|(p, count)| format!("{p}:{count}"))
+                .collect();
+            p.sort();
+            p
+        };
+        assert_eq!(postings(&dict, "apple"), ["a:2", "b:1"]);
+        assert!(postings(&dict, "missing").is_empty());
+        dict.remove_document("a", &m["a"]);
+        assert_eq!(postings(&dict, "apple"), ["b:1"]);
+        assert!(!dict.contains(&stem("pear"), QueryMode::Stemmed));
+        dict.add_document("a", &m["a"]);
+        assert_eq!(postings(&dict, "pear"), ["a:1"]);
+        // only the candidates get scored, the idf still counts every document
+        assert_eq!(query(&m, &["kiwi"]), ["c"]);
+    }
+
+    #[test]
+    fn query_term_weights() {
+        let m = model(&[("a", "alpha filler"), ("b", "beta filler")]);
+        assert_eq!(query(&m, &["alpha", "beta^3"]), ["b", "a"]);
+        assert_eq!(query(&m, &["alpha^3", "beta"]), ["a", "b"]);
+    }
+
+    fn analyze(name: &str) -> Vec<(String, Document)> {
+        analyze_file(&fixture(name), &IndexOptions::default()).unwrap()
+    }
+
+    // the single document of a file that isn't an archive
+    fn analyze_one(name: &str) -> Document {
+        let mut docs = analyze(name);
+        assert_eq!(docs.len(), 1);
+        let (path, d) = docs.remove(0);
+        assert_eq!(path, fixture(name).to_string_lossy());
+        assert_ne!(d.modified(), 0);
+        d
+    }
+
+    #[test]
+    fn indexed_chars() {
+        assert_eq!(document("").indexed_chars(), 0);
+        // "hello", "world" and the punctuation
+        assert_eq!(document("Hello, worlds!").indexed_chars(), 12);
+        let opts = IndexOptions {
+            store_raw_terms: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            create_document_from_text("Hello, worlds!", &opts).indexed_chars(),
+            13
+        );
+    }
+
+    #[test]
+    fn path_terms() {
+        assert_eq!(
+            path_words("/docs/taxes/2023/return.pdf"),
+            "docs taxes 2023 return"
+        );
+        assert_eq!(
+            path_words("/docs/bundle.zip!/notes/a.txt"),
+            "docs bundle.zip notes a"
+        );
+        let opts = IndexOptions {
+            path_terms: true,
+            ..Default::default()
+        };
+        let (_, d) = analyze_file(&fixture("notes.txt"), &opts)
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            d.field_count(FIELD_PATH, &stem("fixtures"), QueryMode::Stemmed),
+            2.0
+        );
+        assert_eq!(
+            d.field_count(FIELD_PATH, &stem("notes"), QueryMode::Stemmed),
+            2.0
+        );
+        // not part of the body
+        assert_eq!(count(&d, "fixtures"), 0);
+    }
+
+    #[test]
+    fn analyze_txt() {
+        let d = analyze_one("notes.txt");
+        assert_eq!(d.file_type(), Some(FileType::Txt));
+        assert_eq!(count(&d, "tomatoes"), 1);
+        assert_eq!(count(&d, "watering"), 1);
+    }
+
+    #[test]
+    fn analyze_xhtml() {
+        let d = analyze_one("page.xhtml");
+        assert_eq!(d.file_type(), Some(FileType::Xml));
+        assert_eq!(count(&d, "magma"), 1);
+        assert_eq!(count(&d, "html"), 0);
+        // boosted, see `field_boost`
+        assert_eq!(
+            d.field_count(FIELD_CAPTION, &stem("lava"), QueryMode::Stemmed),
+            2.0
+        );
+    }
+
+    #[test]
+    fn analyze_pdf() {
+        let d = analyze_one("paper.pdf");
+        assert_eq!(d.file_type(), Some(FileType::Pdf));
+        assert_eq!(count(&d, "galaxies"), 1);
+    }
+
+    #[test]
+    fn analyze_eml() {
+        let d = analyze_one("message.eml");
+        assert_eq!(d.file_type(), Some(FileType::Email));
+        assert_eq!(count(&d, "spreadsheet"), 1);
+        // the name and the address
+        assert_eq!(count(&d, "ada"), 2);
+        // boosted, see `field_boost`
+        assert_eq!(
+            d.field_count(FIELD_SUBJECT, &stem("budget"), QueryMode::Stemmed),
+            3.0
+        );
+    }
+
+    #[test]
+    fn analyze_subtitles() {
+        let srt = analyze_one("lecture.srt");
+        assert_eq!(srt.file_type(), Some(FileType::Subtitle));
+        assert_eq!(count(&srt, "photosynthesis"), 1);
+        // no cue numbers or timestamps
+        assert_eq!(count(&srt, "1"), 0);
+        assert_eq!(count(&srt, "00"), 0);
+        let vtt = analyze_one("talk.vtt");
+        assert_eq!(count(&vtt, "mitochondria"), 1);
+        assert_eq!(count(&vtt, "note"), 0);
+        assert_eq!(count(&vtt, "speaker"), 0);
+    }
+
+    #[test]
+    fn analyze_markdown() {
+        let d = analyze_one("guide.md");
+        assert_eq!(d.file_type(), Some(FileType::Markdown));
+        assert_eq!(count(&d, "glacier"), 1);
+        assert_eq!(count(&d, "crevasses"), 1);
+        assert_eq!(count(&d, "crampons"), 1);
+        assert_eq!(count(&d, "rope"), 2);
+        // no syntax or urls
+        for term in [
+            "#", "*", "`", "[", "]", "(", "!", ">", "_", "---", "https", "png",
+        ] {
+            assert_eq!(count(&d, term), 0, "{term}");
+        }
+    }
+
+    #[test]
+    fn reindex_only_changed_files() {
+        let dir = std::env::temp_dir().join(format!("local-search-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (kept, changed, deleted) = (dir.join("a.txt"), dir.join("b.txt"), dir.join("c.txt"));
+        for p in [&kept, &changed, &deleted] {
+            std::fs::write(p, "apples").unwrap();
+        }
+        let opts = IndexOptions::default();
+        let mut previous = analyze_dir(&dir, &opts).unwrap();
+        assert_eq!(previous.len(), 3);
+        std::fs::write(&changed, "oranges and more").unwrap();
+        std::fs::remove_file(&deleted).unwrap();
+        let model = analyze_dir_incremental(&dir, &opts, &mut previous).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let key = |p: &std::path::Path| p.to_string_lossy().to_string();
+        assert_eq!(model.len(), 2);
+        assert_eq!(count(&model[&key(&kept)], "apples"), 1);
+        assert_eq!(count(&model[&key(&changed)], "oranges"), 1);
+        // the unchanged document got moved over, the old one of the changed file and the deleted
+        // one are left behind
+        assert_eq!(previous.len(), 2);
+        assert!(previous.contains_key(&key(&deleted)));
+    }
+
+    #[test]
+    fn scan_reports_every_file() {
+        let dir = std::env::temp_dir().join(format!("local-search-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "apples").unwrap();
+        std::fs::write(dir.join("sub/b.txt"), "pears").unwrap();
+        std::fs::write(dir.join("sub/broken.pdf"), "not a pdf").unwrap();
+        let opts = IndexOptions::default();
+        let model = analyze_dir(&dir, &opts).unwrap();
+        std::fs::write(dir.join("sub/b.txt"), "pears and plums").unwrap();
+        let done = std::sync::Mutex::new(vec![]);
+        let scan = scan_dir(&dir, &opts, &file_stamps(&model), &|p| {
+            done.lock().unwrap().push(p.to_path_buf());
+            std::ops::ControlFlow::Continue(())
+        })
+        .unwrap();
+        let total = count_files(&dir, &opts);
+        std::fs::remove_dir_all(&dir).unwrap();
+        // the unchanged and the broken files count too
+        assert_eq!(total, 3);
+        assert_eq!(done.into_inner().unwrap().len(), 3);
+        assert_eq!(scan.docs.len(), 1);
+        assert_eq!(scan.unchanged.len(), 1);
+    }
+
+    #[test]
+    fn broken_files_get_skipped() {
+        let dir = std::env::temp_dir().join(format!("local-search-broken-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("broken.pdf"), "not a pdf").unwrap();
+        std::fs::write(dir.join("sub/broken.zip"), "not a zip").unwrap();
+        std::fs::write(dir.join("sub/fine.txt"), "still indexed").unwrap();
+        let model = analyze_dir(&dir, &IndexOptions::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(model.len(), 1);
+        assert!(model.keys().all(|k| k.ends_with("fine.txt")));
+    }
+
+    #[test]
+    fn missing_directory_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("local-search-missing-{}", std::process::id()));
+        let e = analyze_dir(&dir, &IndexOptions::default()).unwrap_err();
+        assert_eq!(e.path, dir);
+        assert!(matches!(e.kind, IndexErrorKind::ReadDir(_)));
+    }
+
+    #[test]
+    fn analyze_zip_entries() {
+        let docs = analyze("bundle.zip");
+        assert_eq!(docs.len(), 1);
+        let (path, d) = &docs[0];
+        assert!(path.ends_with(&format!("bundle.zip{ARCHIVE_SEPARATOR}inner/readme.txt")));
+        assert_eq!(count(d, "penguins"), 1);
+    }
+
+    #[test]
+    fn analyze_mbox_messages() {
+        let mut docs = analyze("inbox.mbox");
+        docs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(docs.len(), 2);
+        assert!(
+            docs[0]
+                .0
+                .ends_with(&format!("inbox.mbox{ARCHIVE_SEPARATOR}0.eml"))
+        );
+        assert_eq!(count(&docs[0].1, "boots"), 1);
+        assert_eq!(count(&docs[1].1, "saffron"), 1);
+    }
+
+    #[test]
+    fn analyze_unknown_extension() {
+        let e = analyze_file(&fixture("missing.bin"), &IndexOptions::default()).unwrap_err();
+        assert!(matches!(e.kind, IndexErrorKind::Unsupported));
+    }
 }